@@ -0,0 +1,97 @@
+//! Compares tree walking evaluation against compiled programs, batch and parallel evaluation.
+//!
+//! Run with `cargo bench`.
+
+use std::{
+    collections::HashMap,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use math::{Function, Range};
+
+const POINTS: usize = 1_000_000;
+const GRID: usize = 2_000;
+
+fn time(name: &str, mut run: impl FnMut() -> f64) -> Duration {
+    let start = Instant::now();
+    black_box(run());
+    let elapsed = start.elapsed();
+    println!("{name:>12}: {elapsed:?}");
+    elapsed
+}
+
+fn main() {
+    let f: Function = "3*x^2 + 2*y - 1 + sin(x*y) / (1 + x^2)"
+        .parse()
+        .expect("the benchmark function is valid");
+    let program = f.compile().expect("all variables are arguments");
+    let points = (0..POINTS).map(|i| (i as f64 / POINTS as f64, 0.5));
+
+    println!("Evaluating {} at {POINTS} points", f.term);
+
+    let tree = time("solve_for", || {
+        points
+            .clone()
+            .map(|(x, y)| {
+                let args = HashMap::from([(f.arguments[0], x), (f.arguments[1], y)]);
+                f.solve_for(&args).unwrap()
+            })
+            .sum()
+    });
+
+    let compiled = time("compiled", || {
+        points
+            .clone()
+            .map(|(x, y)| program.eval(&[x, y]).unwrap())
+            .sum()
+    });
+
+    let rows: Vec<[f64; 2]> = points.clone().map(|(x, y)| [x, y]).collect();
+    let batched = time("eval_rows", || {
+        program
+            .eval_rows(&rows)
+            .into_iter()
+            .map(Result::unwrap)
+            .sum()
+    });
+
+    let (xs, ys): (Vec<f64>, Vec<f64>) = points.clone().unzip();
+    let columnar = time("eval_columns", || {
+        program.eval_columns(&[&xs, &ys]).unwrap().into_iter().sum()
+    });
+
+    for (name, elapsed) in [
+        ("compiled", compiled),
+        ("eval_rows", batched),
+        ("eval_columns", columnar),
+    ] {
+        println!(
+            "{name:>12}: {:.1}x faster than solve_for",
+            tree.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+
+    println!("Evaluating {} on a {GRID}x{GRID} grid", f.term);
+
+    let axis = Range::new(-1., 1., GRID);
+    let serial = time("1 thread", || {
+        program
+            .eval_grid_parallel(axis, axis, 1)
+            .unwrap()
+            .into_iter()
+            .sum()
+    });
+    let parallel = time("all cores", || {
+        program
+            .eval_grid_parallel(axis, axis, 0)
+            .unwrap()
+            .into_iter()
+            .sum()
+    });
+
+    println!(
+        "   all cores: {:.1}x faster than 1 thread",
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
@@ -0,0 +1,267 @@
+//! Symbolic integration of terms by a table of rules.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{
+    Constant, Function, FunctionTerm, Identifier, MyError, Operation, UnaryOperation, Value,
+};
+
+impl Function {
+    /// An antiderivative with respect to `variable`, simplified and taking the same arguments.
+    ///
+    /// Handles sums, constant factors, powers, exponentials, logarithms and the trigonometric and
+    /// hyperbolic functions of terms that are linear in `variable`.
+    pub fn integral(&self, variable: Identifier) -> Result<Function> {
+        Ok(Function {
+            arguments: self.arguments.clone(),
+            term: self.term.integral(variable)?.simplify(),
+        })
+    }
+}
+
+impl FunctionTerm {
+    /// An antiderivative with respect to `variable`, without the constant of integration.
+    pub fn integral(&self, variable: Identifier) -> Result<FunctionTerm> {
+        self.integral_by_rules(variable, false)
+    }
+
+    /// Applies the first rule that matches, and when none does, tries again once on the
+    /// simplified term, which turns `x * x` into `x^2` and collects constant factors.
+    fn integral_by_rules(&self, x: Identifier, simplified: bool) -> Result<FunctionTerm> {
+        if !self.contains(x) {
+            return Ok(self.clone() * FunctionTerm::Variable(x));
+        }
+
+        let integral = match self {
+            Self::Variable(_) => Some(FunctionTerm::Variable(x).pow(2.0.into()) / 2.0.into()),
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let (f, g) = (left.as_ref(), right.as_ref());
+                match operation {
+                    Operation::Plus => Some(f.integral(x)? + g.integral(x)?),
+                    Operation::Minus => Some(f.integral(x)? - g.integral(x)?),
+                    Operation::Multiply if !f.contains(x) => Some(f.clone() * g.integral(x)?),
+                    Operation::Multiply if !g.contains(x) => Some(f.integral(x)? * g.clone()),
+                    Operation::Divide if !g.contains(x) => Some(f.integral(x)? / g.clone()),
+                    Operation::Divide if !f.contains(x) => {
+                        Some(f.clone() * reciprocal(g).integral(x)?)
+                    }
+                    Operation::Pow => power(f, g, x),
+                    _ => None,
+                }
+            }
+            Self::Unary {
+                term,
+                operation: UnaryOperation::Negate,
+            } => Some(-term.integral(x)?),
+            Self::Unary { term, operation } => unary(*operation, term, x),
+            Self::Value(_) => unreachable!("values never contain a variable"),
+        };
+
+        match integral {
+            Some(integral) => Ok(integral),
+            None if !simplified && self.simplify() != *self => {
+                self.simplify().integral_by_rules(x, true)
+            }
+            None => Err(MyError::CannotIntegrate {
+                term: self.to_string(),
+            })?,
+        }
+    }
+}
+
+/// `1 / g` as a power, so that `c / x^2` is integrated as `c * x^-2`.
+fn reciprocal(g: &FunctionTerm) -> FunctionTerm {
+    match g {
+        FunctionTerm::Calculation {
+            left,
+            right,
+            operation: Operation::Pow,
+        } => left.as_ref().clone().pow(-right.as_ref().clone()),
+        g => g.clone().pow((-1.).into()),
+    }
+}
+
+/// The slope of `u` if it is linear in `x`, which is what the rules for `f(u)` divide by.
+fn slope(u: &FunctionTerm, x: Identifier) -> Option<FunctionTerm> {
+    let slope = u.derivative(x).simplify();
+    match slope.contains(x) || slope == 0.0.into() {
+        true => None,
+        false => Some(slope),
+    }
+}
+
+/// The value of a term without the variable, if it only consists of numbers.
+fn number(term: &FunctionTerm) -> Option<f64> {
+    term.solve::<f64>(&HashMap::new()).ok()
+}
+
+fn power(base: &FunctionTerm, exponent: &FunctionTerm, x: Identifier) -> Option<FunctionTerm> {
+    // Power rule: ∫ u^n = u^(n + 1) / (n + 1), or ln|u| for n = -1.
+    if !exponent.contains(x) {
+        let a = slope(base, x)?;
+        let integral = match number(exponent) == Some(-1.) {
+            true => base.clone().unary(UnaryOperation::Abs).ln(),
+            false => {
+                let n = exponent.clone() + 1.0.into();
+                base.clone().pow(n.clone()) / n
+            }
+        };
+        return Some(integral / a);
+    }
+
+    // Exponential rule: ∫ c^u = c^u / ln(c)
+    if !base.contains(x) {
+        let a = slope(exponent, x)?;
+        let power = base.clone().pow(exponent.clone());
+        return Some(match base {
+            FunctionTerm::Value(Value::Constant(Constant::E)) => power / a,
+            _ => power / (a * base.clone().ln()),
+        });
+    }
+
+    None
+}
+
+fn unary(operation: UnaryOperation, u: &FunctionTerm, x: Identifier) -> Option<FunctionTerm> {
+    let a = slope(u, x)?;
+    let u = u.clone();
+    let one = || FunctionTerm::from(1.);
+    let apply = |operation| u.clone().unary(operation);
+    // ∫ ln(u) = u * ln(u) - u
+    let u_ln_u = || u.clone() * u.clone().ln() - u.clone();
+
+    let integral = match operation {
+        UnaryOperation::Abs => u.clone() * apply(UnaryOperation::Abs) / 2.0.into(),
+        UnaryOperation::Sqrt => FunctionTerm::from(2.) * u.clone().pow(1.5.into()) / 3.0.into(),
+        UnaryOperation::Exp => apply(UnaryOperation::Exp),
+        UnaryOperation::Ln => u_ln_u(),
+        UnaryOperation::Log(base) => u_ln_u() / FunctionTerm::from(base).ln(),
+        UnaryOperation::Sin => -apply(UnaryOperation::Cos),
+        UnaryOperation::Cos => apply(UnaryOperation::Sin),
+        UnaryOperation::Tan => -apply(UnaryOperation::Cos).unary(UnaryOperation::Abs).ln(),
+        UnaryOperation::Asin => {
+            u.clone() * apply(UnaryOperation::Asin)
+                + (one() - u.clone().pow(2.0.into())).unary(UnaryOperation::Sqrt)
+        }
+        UnaryOperation::Acos => {
+            u.clone() * apply(UnaryOperation::Acos)
+                - (one() - u.clone().pow(2.0.into())).unary(UnaryOperation::Sqrt)
+        }
+        UnaryOperation::Atan => {
+            u.clone() * apply(UnaryOperation::Atan)
+                - (one() + u.clone().pow(2.0.into())).ln() / 2.0.into()
+        }
+        UnaryOperation::Sinh => apply(UnaryOperation::Cosh),
+        UnaryOperation::Cosh => apply(UnaryOperation::Sinh),
+        UnaryOperation::Tanh => apply(UnaryOperation::Cosh).ln(),
+        UnaryOperation::Negate
+        | UnaryOperation::Asinh
+        | UnaryOperation::Acosh
+        | UnaryOperation::Atanh
+        | UnaryOperation::Floor
+        | UnaryOperation::Ceil
+        | UnaryOperation::Round
+        | UnaryOperation::Sign => return None,
+    };
+    Some(integral / a)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{FunctionTerm, Identifier};
+
+    /// Differentiates the antiderivative of `term` back and compares it with `term` wherever
+    /// both can be evaluated.
+    fn assert_antiderivative(term: &str) {
+        let x = Identifier::new("x");
+        let term: FunctionTerm = term.parse().unwrap();
+        let integral = term.integral(x).unwrap();
+        let derivative = integral.derivative(x);
+
+        let mut compared = 0;
+        for x_value in [-2.5, -0.7, -0.2, 0.15, 0.35, 0.6, 0.85, 1.7, 3.2] {
+            let args = HashMap::from([(x, x_value)]);
+            let (Ok(expected), Ok(actual)) = (term.solve(&args), derivative.solve::<f64>(&args))
+            else {
+                continue;
+            };
+            if !expected.is_finite() {
+                continue;
+            }
+            let error = (actual - expected).abs() / expected.abs().max(1.);
+            assert!(
+                error < 1e-9,
+                "{term}: {actual} instead of {expected} at {x_value}"
+            );
+            compared += 1;
+        }
+        assert!(compared > 0, "{term} was never compared");
+    }
+
+    #[test]
+    fn antiderivatives_differentiate_back() {
+        for term in [
+            // Constants, sums and constant factors.
+            "3",
+            "pi",
+            "x",
+            "x + 2",
+            "x - sin(x)",
+            "3 * x",
+            "x * 3",
+            "x / 4",
+            "2 / x",
+            "2 / x^3",
+            "-x",
+            // Powers and exponentials of linear terms.
+            "x^2",
+            "x^-2",
+            "x^0.5",
+            "(2 * x + 1)^3",
+            "(3 * x - 1)^-1",
+            "1 / (x + 4)",
+            "2^x",
+            "e^(3 * x)",
+            "10^(2 * x - 1)",
+            // Functions of linear terms.
+            "abs(2 * x - 1)",
+            "sqrt(3 * x + 8)",
+            "exp(-x)",
+            "ln(2 * x)",
+            "log(2, x / 3)",
+            "log(x)",
+            "sin(2 * x)",
+            "cos(x / 2 + 1)",
+            "tan(x / 3)",
+            "asin(x / 4)",
+            "acos(x / 4)",
+            "atan(2 * x)",
+            "sinh(x - 1)",
+            "cosh(2 * x)",
+            "tanh(x / 2)",
+            // Only integrable once simplified.
+            "x * x",
+            "x * 2 * x",
+            "(x + x) * 3",
+        ] {
+            assert_antiderivative(term);
+        }
+    }
+
+    #[test]
+    fn unknown_antiderivatives_fail() {
+        let x = Identifier::new("x");
+        for term in ["sin(x^2)", "x * sin(x)", "asinh(x)", "floor(x)"] {
+            let term: FunctionTerm = term.parse().unwrap();
+            assert!(term.integral(x).is_err(), "{term}");
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Evaluation of a function over many argument sets in one call.
+
+use anyhow::{Context, Result};
+
+use crate::{compile::Instruction, Function, MyError, Program};
+
+impl Function {
+    /// Evaluates every row, each holding the arguments in the order of [`Function::arguments`].
+    pub fn eval_rows<R: AsRef<[f64]>>(&self, rows: &[R]) -> Result<Vec<Result<f64>>> {
+        Ok(self.compile()?.eval_rows(rows))
+    }
+
+    /// Evaluates row by row over one column of values per argument.
+    pub fn eval_columns(&self, columns: &[&[f64]]) -> Result<Vec<f64>> {
+        self.compile()?.eval_columns(columns)
+    }
+}
+
+impl Program {
+    /// Evaluates every row, reporting errors per row.
+    pub fn eval_rows<R: AsRef<[f64]>>(&self, rows: &[R]) -> Vec<Result<f64>> {
+        let mut stack = Vec::with_capacity(self.stack_size);
+
+        rows.iter()
+            .map(|row| {
+                let row = row.as_ref();
+                if row.len() != self.arity {
+                    Err(MyError::ArityMismatch {
+                        expected: self.arity,
+                        given: row.len(),
+                    })?
+                }
+                self.eval_with(row, &mut stack)
+            })
+            .collect()
+    }
+
+    /// Evaluates over one column per argument, running each instruction over all rows at once.
+    ///
+    /// Fails on the first row that cannot be evaluated.
+    pub fn eval_columns(&self, columns: &[&[f64]]) -> Result<Vec<f64>> {
+        if columns.len() != self.arity {
+            Err(MyError::ArityMismatch {
+                expected: self.arity,
+                given: columns.len(),
+            })?
+        }
+
+        let rows = columns.first().map_or(1, |column| column.len());
+        if let Some(column) = columns.iter().find(|column| column.len() != rows) {
+            Err(MyError::ColumnLengthMismatch {
+                expected: rows,
+                given: column.len(),
+            })?
+        }
+
+        let mut stack: Vec<Vec<f64>> = Vec::with_capacity(self.stack_size);
+        // Popped columns are kept around so their allocations can be reused.
+        let mut free: Vec<Vec<f64>> = Vec::new();
+
+        for instruction in &self.instructions {
+            match *instruction {
+                Instruction::Load(slot) => {
+                    let mut column = free.pop().unwrap_or_default();
+                    column.clear();
+                    column.extend_from_slice(columns[slot]);
+                    stack.push(column);
+                }
+                Instruction::Constant(x) => {
+                    let mut column = free.pop().unwrap_or_default();
+                    column.clear();
+                    column.resize(rows, x);
+                    stack.push(column);
+                }
+                Instruction::Binary(operation) => {
+                    let right = stack.pop().expect("compiled programs are balanced");
+                    let left = stack.last_mut().expect("compiled programs are balanced");
+                    for (row, (left, right)) in left.iter_mut().zip(&right).enumerate() {
+                        *left = operation
+                            .apply(*left, *right)
+                            .with_context(|| format!("Failed to evaluate row {row}."))?;
+                    }
+                    free.push(right);
+                }
+                Instruction::Unary(operation) => {
+                    let column = stack.last_mut().expect("compiled programs are balanced");
+                    for (row, x) in column.iter_mut().enumerate() {
+                        *x = operation
+                            .apply(*x)
+                            .with_context(|| format!("Failed to evaluate row {row}."))?;
+                    }
+                }
+            }
+        }
+
+        Ok(stack.pop().expect("compiled programs are balanced"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, MyError};
+
+    fn error(error: anyhow::Error) -> MyError {
+        error.downcast().unwrap()
+    }
+
+    #[test]
+    fn rows() {
+        let f: Function = "x / y".parse().unwrap();
+        let rows: &[&[f64]] = &[&[1., 2.], &[3., 0.], &[4.], &[9., 3.]];
+        let values = f.eval_rows(rows).unwrap();
+        assert_eq!(values.len(), rows.len());
+
+        let mut values = values.into_iter();
+        assert_eq!(values.next().unwrap().unwrap(), 0.5);
+        assert!(matches!(
+            error(values.next().unwrap().unwrap_err()),
+            MyError::DivisionByZero
+        ));
+        assert!(matches!(
+            error(values.next().unwrap().unwrap_err()),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        // Earlier failures do not affect later rows.
+        assert_eq!(values.next().unwrap().unwrap(), 3.);
+
+        assert!(f.eval_rows::<[f64; 2]>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn columns() {
+        let f: Function = "x * y + 1".parse().unwrap();
+        let x = [1., 2., 3.];
+        let y = [4., 5., 6.];
+        assert_eq!(f.eval_columns(&[&x, &y]).unwrap(), [5., 11., 19.]);
+        assert!(f.eval_columns(&[&[], &[]]).unwrap().is_empty());
+
+        let rows: Vec<f64> = f
+            .eval_rows(&[[1., 4.], [2., 5.], [3., 6.]])
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows, f.eval_columns(&[&x, &y]).unwrap());
+
+        // Without arguments there is a single row.
+        let constant: Function = "2 + 3".parse().unwrap();
+        assert_eq!(constant.eval_columns(&[]).unwrap(), [5.]);
+    }
+
+    #[test]
+    fn column_shapes_are_checked() {
+        let f: Function = "x * y".parse().unwrap();
+        assert!(matches!(
+            error(f.eval_columns(&[&[1., 2.]]).unwrap_err()),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        assert!(matches!(
+            error(f.eval_columns(&[&[1., 2.], &[1.]]).unwrap_err()),
+            MyError::ColumnLengthMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn failing_rows_are_named() {
+        let f: Function = "sqrt(x)".parse().unwrap();
+        let error = f.eval_columns(&[&[4., 1., -1., -2.]]).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to evaluate row 2.");
+        assert!(matches!(
+            error.downcast().unwrap(),
+            MyError::RootOfNegative { .. }
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+//! Compilation of terms into a flat stack machine program for fast repeated evaluation.
+
+use anyhow::Result;
+
+use crate::{Function, FunctionTerm, MyError, Operation, UnaryOperation};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Instruction {
+    /// Pushes the argument in the given slot.
+    Load(usize),
+    Constant(f64),
+    Binary(Operation),
+    Unary(UnaryOperation),
+}
+
+/// A compiled [`Function`], with every variable resolved to the index of its argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub(crate) instructions: Vec<Instruction>,
+    pub(crate) arity: usize,
+    pub(crate) stack_size: usize,
+}
+
+impl Function {
+    pub fn compile(&self) -> Result<Program> {
+        let mut program = Program {
+            instructions: Vec::new(),
+            arity: self.arguments.len(),
+            stack_size: 0,
+        };
+        program.emit(self, &self.term, 0)?;
+        Ok(program)
+    }
+}
+
+impl Program {
+    /// Appends the instructions for `term`, with `depth` values already on the stack.
+    fn emit(&mut self, function: &Function, term: &FunctionTerm, depth: usize) -> Result<()> {
+        self.stack_size = self.stack_size.max(depth + 1);
+
+        match term {
+            FunctionTerm::Variable(x) => {
+                let slot = function
+                    .arguments
+                    .iter()
+                    .position(|argument| argument == x)
+                    .ok_or(MyError::NoSuchVariable { variable: *x })?;
+                self.instructions.push(Instruction::Load(slot));
+            }
+            FunctionTerm::Value(x) => self.instructions.push(Instruction::Constant(x.get()?)),
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                self.emit(function, left, depth)?;
+                self.emit(function, right, depth + 1)?;
+                self.push_folded(Instruction::Binary(*operation));
+            }
+            FunctionTerm::Unary { term, operation } => {
+                self.emit(function, term, depth)?;
+                self.push_folded(Instruction::Unary(*operation));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes an operation, computing it right away if its operands are constant.
+    fn push_folded(&mut self, instruction: Instruction) {
+        let folded = match (instruction, self.instructions.as_slice()) {
+            (
+                Instruction::Binary(operation),
+                [.., Instruction::Constant(left), Instruction::Constant(right)],
+            ) => operation.apply(*left, *right).ok().map(|x| (2, x)),
+            (Instruction::Unary(operation), [.., Instruction::Constant(x)]) => {
+                operation.apply(*x).ok().map(|x| (1, x))
+            }
+            _ => None,
+        };
+
+        // Failing operations are left in place so the error surfaces on evaluation.
+        match folded {
+            Some((operands, x)) => {
+                self.instructions
+                    .truncate(self.instructions.len() - operands);
+                self.instructions.push(Instruction::Constant(x));
+            }
+            None => self.instructions.push(instruction),
+        }
+    }
+
+    /// Evaluates the program with the arguments in the order of [`Function::arguments`].
+    pub fn eval(&self, args: &[f64]) -> Result<f64> {
+        if args.len() != self.arity {
+            Err(MyError::ArityMismatch {
+                expected: self.arity,
+                given: args.len(),
+            })?
+        }
+
+        self.eval_with(args, &mut Vec::with_capacity(self.stack_size))
+    }
+
+    /// Evaluates without checking the arity, reusing `stack` for intermediate values.
+    pub(crate) fn eval_with(&self, args: &[f64], stack: &mut Vec<f64>) -> Result<f64> {
+        stack.clear();
+        for instruction in &self.instructions {
+            let value = match *instruction {
+                Instruction::Load(slot) => args[slot],
+                Instruction::Constant(x) => x,
+                Instruction::Binary(operation) => {
+                    let right = stack.pop().expect("compiled programs are balanced");
+                    let left = stack.pop().expect("compiled programs are balanced");
+                    operation.apply(left, right)?
+                }
+                Instruction::Unary(operation) => {
+                    operation.apply(stack.pop().expect("compiled programs are balanced"))?
+                }
+            };
+            stack.push(value);
+        }
+
+        Ok(stack.pop().expect("compiled programs are balanced"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, FunctionTerm, Identifier, MyError};
+
+    use super::Instruction;
+
+    fn error(result: anyhow::Result<f64>) -> MyError {
+        result.unwrap_err().downcast().unwrap()
+    }
+
+    #[test]
+    fn programs_agree_with_solving() {
+        for input in [
+            "3*x^2 + 2*y - 1",
+            "sin(x*y) / (1 + x^2)",
+            "-x^y + log(2, y) - abs(x - y)",
+            "sqrt(y) * exp(-x) + tanh(x) * pi",
+            "floor(x * 10) / 10 + sign(x - 1) + x / y / 2",
+        ] {
+            let f: Function = input.parse().unwrap();
+            let program = f.compile().unwrap();
+            for (x, y) in [(0.5, 2.), (-1.25, 0.75), (3., 10.)] {
+                let expected = f.solve_args_in_order(vec![x, y]).unwrap();
+                let actual = program.eval(&[x, y]).unwrap();
+                let close = (actual - expected).abs() <= 1e-15 * expected.abs();
+                assert!(
+                    actual == expected || close || actual.is_nan() && expected.is_nan(),
+                    "{input} at ({x}, {y}): {actual} instead of {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        let f: Function = "x * (2 + 3) + sqrt(16)".parse().unwrap();
+        let program = f.compile().unwrap();
+        assert_eq!(
+            program.instructions,
+            [
+                Instruction::Load(0),
+                Instruction::Constant(5.),
+                Instruction::Binary(crate::Operation::Multiply),
+                Instruction::Constant(4.),
+                Instruction::Binary(crate::Operation::Plus),
+            ]
+        );
+        assert_eq!(program.eval(&[2.]).unwrap(), 14.);
+    }
+
+    #[test]
+    fn errors_surface_on_evaluation() {
+        let f: Function = "x / (1 - 1) + sqrt(x)".parse().unwrap();
+        let program = f.compile().unwrap();
+        assert!(matches!(
+            error(program.eval(&[1.])),
+            MyError::DivisionByZero
+        ));
+
+        let f: Function = "sqrt(x)".parse().unwrap();
+        assert!(matches!(
+            error(f.compile().unwrap().eval(&[-1.])),
+            MyError::RootOfNegative { .. }
+        ));
+    }
+
+    #[test]
+    fn arguments_are_checked() {
+        let f: Function = "x + y".parse().unwrap();
+        let program = f.compile().unwrap();
+        assert!(matches!(
+            error(program.eval(&[1.])),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+
+        let term: FunctionTerm = "x + z".parse().unwrap();
+        let unbound = Function {
+            arguments: vec![Identifier::new("x")],
+            term,
+        };
+        let error: MyError = unbound.compile().unwrap_err().downcast().unwrap();
+        assert!(matches!(error, MyError::NoSuchVariable { .. }));
+    }
+}
@@ -0,0 +1,391 @@
+//! Evaluation with complex numbers, where roots and logarithms of negative numbers are defined.
+
+use std::{
+    f64::consts::FRAC_PI_2,
+    fmt::{Display, Formatter},
+};
+
+use anyhow::Result;
+
+use crate::{Constant, MyError, Number, UnaryOperation};
+
+/// A complex number `re + im * i` in double precision.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0., im: 1. };
+
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn is_real(&self) -> bool {
+        self.im == 0.
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The angle to the positive real axis, in `(-pi, pi]`.
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(&self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub(crate) fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+
+    pub(crate) fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+
+    pub(crate) fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+
+    pub(crate) fn scale(self, factor: f64) -> Complex {
+        Complex::new(self.re * factor, self.im * factor)
+    }
+
+    fn exp(self) -> Complex {
+        let (sin, cos) = self.im.sin_cos();
+        Complex::new(cos, sin).scale(self.re.exp())
+    }
+
+    /// The principal logarithm, failing for zero.
+    fn ln(self) -> Result<Complex> {
+        if self == Complex::default() {
+            Err(MyError::LogarithmOfNonPositive { value: 0. })?
+        }
+        Ok(Complex::new(self.norm().ln(), self.arg()))
+    }
+
+    /// The quotient, infinite or NaN when dividing by zero.
+    pub(crate) fn div(self, other: Complex) -> Complex {
+        let norm = other.re * other.re + other.im * other.im;
+        self.mul(other.conj()).scale(1. / norm)
+    }
+
+    /// The principal square root, with the cut along the negative real axis.
+    pub(crate) fn sqrt(self) -> Complex {
+        let norm = self.norm();
+        let re = ((norm + self.re) / 2.).sqrt();
+        let im = ((norm - self.re) / 2.).sqrt().copysign(self.im);
+        Complex::new(re, im)
+    }
+
+    /// The principal cube root.
+    pub(crate) fn cbrt(self) -> Complex {
+        let (sin, cos) = (self.arg() / 3.).sin_cos();
+        Complex::new(cos, sin).scale(self.norm().cbrt())
+    }
+
+    /// Raises to an integer power by repeated squaring, exact for small Gaussian integers.
+    fn powi(self, exponent: i32) -> Result<Complex> {
+        let mut result = Complex::new(1., 0.);
+        let mut base = self;
+        let mut n = exponent.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(base);
+            }
+            base = base.mul(base);
+            n >>= 1;
+        }
+        match exponent < 0 {
+            true => Complex::new(1., 0.).divide(result),
+            false => Ok(result),
+        }
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Complex::new(re, 0.)
+    }
+}
+
+/// Writes `a + bi`, leaving out a zero part, and uses the precision for both parts if given.
+impl Display for Complex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let part = |x: f64| match f.precision() {
+            Some(precision) => format!("{x:.precision$}"),
+            None => format!("{x}"),
+        };
+        let imaginary = |x: f64| match x {
+            1. => "i".to_string(),
+            x => format!("{}i", part(x)),
+        };
+
+        match (self.re, self.im) {
+            (re, 0.) => write!(f, "{}", part(re)),
+            (0., im) if im < 0. => write!(f, "-{}", imaginary(-im)),
+            (0., im) => write!(f, "{}", imaginary(im)),
+            (re, im) if im < 0. => write!(f, "{} - {}", part(re), imaginary(-im)),
+            (re, im) => write!(f, "{} + {}", part(re), imaginary(im)),
+        }
+    }
+}
+
+/// Complex arithmetic. Multivalued operations return their principal value.
+impl Number for Complex {
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(x.into())
+    }
+
+    fn from_constant(constant: Constant) -> Result<Self> {
+        Ok(match constant.value() {
+            Some(x) => x.into(),
+            None => Complex::I,
+        })
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(self.add(other))
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(self.sub(other))
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        Ok(self.mul(other))
+    }
+
+    fn divide(self, other: Self) -> Result<Self> {
+        if other == Complex::default() {
+            Err(MyError::DivisionByZero)?
+        }
+        Ok(self.div(other))
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        if exponent == Complex::default() {
+            return Ok(Complex::new(1., 0.));
+        }
+        if self == Complex::default() {
+            return match exponent.re > 0. {
+                true => Ok(Complex::default()),
+                false => Err(MyError::DivisionByZero)?,
+            };
+        }
+        // Stay real where possible, so `(-2)^3` is exactly -8 and `2^0.5` has no rounding noise.
+        if exponent.is_real() && exponent.re.fract() == 0. && exponent.re.abs() <= i32::MAX as f64 {
+            return self.powi(exponent.re as i32);
+        }
+        if self.is_real() && self.re > 0. && exponent.is_real() {
+            return Ok(self.re.powf(exponent.re).into());
+        }
+        Ok(exponent.mul(self.ln()?).exp())
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        let z = self;
+        let one = Complex::new(1., 0.);
+        let pole = |_| MyError::OutOfDomain {
+            operation,
+            value: z.to_string(),
+        };
+
+        // Real arguments inside the real domain get the more accurate real result.
+        if z.is_real() {
+            if let Ok(x) = z.re.unary(operation) {
+                return Ok(x.into());
+            }
+        }
+
+        let result = match operation {
+            UnaryOperation::Negate => Complex::new(-z.re, -z.im),
+            UnaryOperation::Abs => z.norm().into(),
+            UnaryOperation::Sqrt => z.sqrt(),
+            UnaryOperation::Exp => z.exp(),
+            UnaryOperation::Ln => z.ln()?,
+            UnaryOperation::Log(base) if base <= 0. || base == 1. => {
+                Err(MyError::InvalidLogarithmBase { base })?
+            }
+            UnaryOperation::Log(base) => Complex::new(z.norm().log(base), z.arg() / base.ln()),
+            UnaryOperation::Sin => Complex::new(z.re.sin() * z.im.cosh(), z.re.cos() * z.im.sinh()),
+            UnaryOperation::Cos => {
+                Complex::new(z.re.cos() * z.im.cosh(), -z.re.sin() * z.im.sinh())
+            }
+            UnaryOperation::Tan => z
+                .unary(UnaryOperation::Sin)?
+                .divide(z.unary(UnaryOperation::Cos)?)?,
+            UnaryOperation::Sinh => {
+                Complex::new(z.re.sinh() * z.im.cos(), z.re.cosh() * z.im.sin())
+            }
+            UnaryOperation::Cosh => {
+                Complex::new(z.re.cosh() * z.im.cos(), z.re.sinh() * z.im.sin())
+            }
+            UnaryOperation::Tanh => z
+                .unary(UnaryOperation::Sinh)?
+                .divide(z.unary(UnaryOperation::Cosh)?)?,
+            // asin(z) = -i * ln(iz + sqrt(1 - z^2))
+            UnaryOperation::Asin => {
+                let w = Complex::I.mul(z).add(one.sub(z.mul(z)).sqrt()).ln()?;
+                Complex::new(w.im, -w.re)
+            }
+            UnaryOperation::Acos => Complex::new(FRAC_PI_2, 0.).sub(z.unary(UnaryOperation::Asin)?),
+            // atan(z) = i/2 * (ln(1 - iz) - ln(1 + iz)), with poles at i and -i.
+            UnaryOperation::Atan => {
+                let iz = Complex::I.mul(z);
+                let w = one.sub(iz).ln().and_then(|x| Ok(x.sub(one.add(iz).ln()?)));
+                let w = w.map_err(pole)?;
+                Complex::new(-w.im, w.re).scale(0.5)
+            }
+            // asinh(z) = ln(z + sqrt(z^2 + 1))
+            UnaryOperation::Asinh => z.add(z.mul(z).add(one).sqrt()).ln()?,
+            // acosh(z) = ln(z + sqrt(z + 1) * sqrt(z - 1))
+            UnaryOperation::Acosh => z.add(z.add(one).sqrt().mul(z.sub(one).sqrt())).ln()?,
+            // atanh(z) = (ln(1 + z) - ln(1 - z)) / 2, with poles at 1 and -1.
+            UnaryOperation::Atanh => {
+                let w = one.add(z).ln().and_then(|x| Ok(x.sub(one.sub(z).ln()?)));
+                w.map_err(pole)?.scale(0.5)
+            }
+            UnaryOperation::Floor => Complex::new(z.re.floor(), z.im.floor()),
+            UnaryOperation::Ceil => Complex::new(z.re.ceil(), z.im.ceil()),
+            UnaryOperation::Round => Complex::new(z.re.round(), z.im.round()),
+            UnaryOperation::Sign => z.scale(1. / z.norm()),
+        };
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, f64::consts::PI};
+
+    use crate::{FunctionTerm, Identifier, MyError, Number, UnaryOperation};
+
+    use super::Complex;
+
+    fn eval(input: &str) -> anyhow::Result<Complex> {
+        let term: FunctionTerm = input.parse().unwrap();
+        term.solve(&HashMap::new())
+    }
+
+    fn assert_close(actual: Complex, expected: Complex, what: &str) {
+        let error = actual.sub(expected).norm() / expected.norm().max(1.);
+        assert!(error < 1e-12, "{what}: {actual} instead of {expected}");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("i^2").unwrap(), Complex::new(-1., 0.));
+        assert_eq!(eval("(1 + 2 * i) * (3 - i)").unwrap(), Complex::new(5., 5.));
+        assert_eq!(eval("(-2)^3").unwrap(), Complex::new(-8., 0.));
+        assert_eq!(eval("(1 + i)^-2").unwrap(), Complex::new(0., -0.5));
+        assert_eq!(eval("sqrt(-4)").unwrap(), Complex::new(0., 2.));
+        assert_eq!(eval("2^0.5").unwrap(), Complex::from(2f64.sqrt()));
+        assert_close(
+            eval("(-8)^(1 / 3)").unwrap(),
+            Complex::new(1., 3f64.sqrt()),
+            "cbrt",
+        );
+        assert_close(eval("i^i").unwrap(), Complex::from((-PI / 2.).exp()), "i^i");
+        assert_close(
+            eval("exp(i * pi) + 1").unwrap(),
+            Complex::default(),
+            "euler",
+        );
+        assert_close(eval("ln(-1)").unwrap(), Complex::new(0., PI), "ln");
+        assert_close(
+            eval("log(10, -100)").unwrap(),
+            Complex::new(2., PI / 10f64.ln()),
+            "log",
+        );
+    }
+
+    #[test]
+    fn real_domains_are_extended() {
+        assert_close(
+            eval("asin(2)").unwrap(),
+            Complex::new(PI / 2., -(2. + 3f64.sqrt()).ln()),
+            "asin",
+        );
+        assert_close(
+            eval("acosh(0)").unwrap(),
+            Complex::new(0., PI / 2.),
+            "acosh",
+        );
+        assert_close(
+            eval("atanh(2)").unwrap(),
+            Complex::new(3f64.ln() / 2., -PI / 2.),
+            "atanh",
+        );
+        // Real arguments in the real domain keep the real result.
+        assert_eq!(eval("sin(1)").unwrap(), Complex::from(1f64.sin()));
+    }
+
+    #[test]
+    fn inverses() {
+        let z = Identifier::new("z");
+        for (function, inverse) in [
+            ("sin", "asin"),
+            ("cos", "acos"),
+            ("tan", "atan"),
+            ("sinh", "asinh"),
+            ("cosh", "acosh"),
+            ("tanh", "atanh"),
+            ("exp", "ln"),
+        ] {
+            let term: FunctionTerm = format!("{function}({inverse}(z))").parse().unwrap();
+            for value in [
+                Complex::new(0.3, 0.4),
+                Complex::new(-1.5, 2.),
+                Complex::new(2., -0.5),
+            ] {
+                let actual = term.solve(&HashMap::from([(z, value)])).unwrap();
+                assert_close(actual, value, function);
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |input: &str| -> MyError { eval(input).unwrap_err().downcast().unwrap() };
+        assert!(matches!(error("1 / (i - i)"), MyError::DivisionByZero));
+        assert!(matches!(error("0^(-1 + i)"), MyError::DivisionByZero));
+        assert!(matches!(
+            error("ln(0)"),
+            MyError::LogarithmOfNonPositive { .. }
+        ));
+        assert!(matches!(error("atan(i)"), MyError::OutOfDomain { .. }));
+        assert!(matches!(error("atanh(-1)"), MyError::OutOfDomain { .. }));
+        assert!(matches!(
+            Complex::I
+                .unary(UnaryOperation::Log(1.))
+                .unwrap_err()
+                .downcast()
+                .unwrap(),
+            MyError::InvalidLogarithmBase { .. }
+        ));
+    }
+
+    #[test]
+    fn display() {
+        for (z, text) in [
+            (Complex::new(1.5, 0.), "1.5"),
+            (Complex::new(0., 1.), "i"),
+            (Complex::new(0., -2.), "-2i"),
+            (Complex::new(3., -1.), "3 - i"),
+            (Complex::new(-1., 0.25), "-1 + 0.25i"),
+        ] {
+            assert_eq!(z.to_string(), text);
+        }
+        assert_eq!(format!("{:.2}", Complex::new(1., 2. / 3.)), "1.00 + 0.67i");
+    }
+}
@@ -0,0 +1,175 @@
+//! Symbolic differentiation of terms.
+
+use crate::{Function, FunctionTerm, Identifier, Operation, UnaryOperation};
+
+impl Function {
+    /// The partial derivative with respect to `variable`, taking the same arguments.
+    pub fn derivative(&self, variable: Identifier) -> Function {
+        Function {
+            arguments: self.arguments.clone(),
+            term: self.term.derivative(variable),
+        }
+    }
+
+    /// Differentiates `n` times with respect to `variable`.
+    pub fn nth_derivative(&self, variable: Identifier, n: usize) -> Function {
+        (0..n).fold(self.clone(), |f, _| f.derivative(variable))
+    }
+}
+
+impl FunctionTerm {
+    pub fn derivative(&self, variable: Identifier) -> FunctionTerm {
+        match self {
+            Self::Variable(x) if *x == variable => 1.0.into(),
+            Self::Variable(_) | Self::Value(_) => 0.0.into(),
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let (f, g) = (left.as_ref().clone(), right.as_ref().clone());
+                let (df, dg) = (left.derivative(variable), right.derivative(variable));
+
+                match operation {
+                    Operation::Plus => df + dg,
+                    Operation::Minus => df - dg,
+                    Operation::Multiply => df * g + f * dg,
+                    Operation::Divide => (df * g.clone() - f * dg) / g.pow(2.0.into()),
+                    // Power rule: (f^c)' = c * f^(c - 1) * f'
+                    Operation::Pow if !right.contains(variable) => {
+                        g.clone() * f.pow(g - 1.0.into()) * df
+                    }
+                    // Exponential rule: (c^g)' = c^g * ln(c) * g'
+                    Operation::Pow if !left.contains(variable) => self.clone() * f.ln() * dg,
+                    // General case: (f^g)' = f^g * (g' * ln(f) + g * f' / f)
+                    Operation::Pow => self.clone() * (dg * f.clone().ln() + g * df / f),
+                }
+            }
+            Self::Unary { term, operation } => {
+                let inner = term.derivative(variable);
+                let u = term.as_ref().clone();
+                let one = || FunctionTerm::from(1.);
+                let square = |u: FunctionTerm| u.pow(2.0.into());
+
+                let outer = match operation {
+                    UnaryOperation::Negate => (-1.).into(),
+                    UnaryOperation::Abs => u.unary(UnaryOperation::Sign),
+                    UnaryOperation::Sqrt => one() / (FunctionTerm::from(2.) * self.clone()),
+                    UnaryOperation::Exp => self.clone(),
+                    UnaryOperation::Ln => one() / u,
+                    UnaryOperation::Log(base) => one() / (u * FunctionTerm::from(*base).ln()),
+                    UnaryOperation::Sin => u.unary(UnaryOperation::Cos),
+                    UnaryOperation::Cos => -u.unary(UnaryOperation::Sin),
+                    UnaryOperation::Tan => one() / square(u.unary(UnaryOperation::Cos)),
+                    UnaryOperation::Asin => one() / (one() - square(u)).unary(UnaryOperation::Sqrt),
+                    UnaryOperation::Acos => {
+                        -(one() / (one() - square(u)).unary(UnaryOperation::Sqrt))
+                    }
+                    UnaryOperation::Atan => one() / (one() + square(u)),
+                    UnaryOperation::Sinh => u.unary(UnaryOperation::Cosh),
+                    UnaryOperation::Cosh => u.unary(UnaryOperation::Sinh),
+                    UnaryOperation::Tanh => one() / square(u.unary(UnaryOperation::Cosh)),
+                    UnaryOperation::Asinh => {
+                        one() / (square(u) + one()).unary(UnaryOperation::Sqrt)
+                    }
+                    UnaryOperation::Acosh => {
+                        one() / (square(u) - one()).unary(UnaryOperation::Sqrt)
+                    }
+                    UnaryOperation::Atanh => one() / (one() - square(u)),
+                    // Piecewise constant, so the derivative is zero wherever it exists.
+                    UnaryOperation::Floor
+                    | UnaryOperation::Ceil
+                    | UnaryOperation::Round
+                    | UnaryOperation::Sign => 0.0.into(),
+                };
+                outer * inner
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Function, FunctionTerm, Identifier};
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        let error = (actual - expected).abs() / expected.abs().max(1.);
+        assert!(error < 1e-12, "{what}: {actual} instead of {expected}");
+    }
+
+    /// Compares the derivative of `f` with respect to `x` against `expected`.
+    fn assert_derivative(f: &str, expected: &str) {
+        let x = Identifier::new("x");
+        let f: FunctionTerm = f.parse().unwrap();
+        let expected: FunctionTerm = expected.parse().unwrap();
+        let derivative = f.derivative(x);
+        for value in [0.3, 0.55, 0.9] {
+            let args = HashMap::from([(x, value), (Identifier::new("y"), 1.7)]);
+            let actual = derivative.solve::<f64>(&args).unwrap();
+            assert_close(actual, expected.solve(&args).unwrap(), &f.to_string());
+        }
+    }
+
+    #[test]
+    fn rules() {
+        for (f, expected) in [
+            ("3", "0"),
+            ("x", "1"),
+            ("y", "0"),
+            ("x + y", "1"),
+            ("x - 2 * x", "-1"),
+            ("x * y", "y"),
+            ("x^2 * sin(x)", "2 * x * sin(x) + x^2 * cos(x)"),
+            ("x / (x + 1)", "1 / (x + 1)^2"),
+            ("x^5", "5 * x^4"),
+            ("x^y", "y * x^(y - 1)"),
+            ("2^x", "2^x * ln(2)"),
+            ("x^x", "x^x * (ln(x) + 1)"),
+            ("-x^3", "-3 * x^2"),
+        ] {
+            assert_derivative(f, expected);
+        }
+    }
+
+    #[test]
+    fn chain_rule() {
+        for (f, expected) in [
+            ("sin(x^2)", "cos(x^2) * 2 * x"),
+            ("exp(3 * x)", "3 * exp(3 * x)"),
+            ("ln(x^2 + 1)", "2 * x / (x^2 + 1)"),
+            ("sqrt(4 * x)", "2 / sqrt(4 * x)"),
+            ("log(2, x)", "1 / (x * ln(2))"),
+            ("abs(x - 1)", "-1"),
+            ("cos(y * x)", "-sin(y * x) * y"),
+            ("tan(x)", "1 / cos(x)^2"),
+            ("asin(x)", "1 / sqrt(1 - x^2)"),
+            ("acos(x)", "-1 / sqrt(1 - x^2)"),
+            ("atan(x)", "1 / (1 + x^2)"),
+            ("sinh(x)", "cosh(x)"),
+            ("cosh(x)", "sinh(x)"),
+            ("tanh(x)", "1 / cosh(x)^2"),
+            ("asinh(x)", "1 / sqrt(x^2 + 1)"),
+            ("acosh(x + 1)", "1 / sqrt((x + 1)^2 - 1)"),
+            ("atanh(x)", "1 / (1 - x^2)"),
+            ("floor(x) + sign(x)", "0"),
+        ] {
+            assert_derivative(f, expected);
+        }
+    }
+
+    #[test]
+    fn higher_derivatives() {
+        let f: Function = "x^5 + y * x".parse().unwrap();
+        let x = f.arguments[0];
+        let third = f.nth_derivative(x, 3);
+        assert_eq!(third.arguments, f.arguments);
+        assert_close(
+            third.solve_args_in_order(vec![2., 7.]).unwrap(),
+            240.,
+            "x^5",
+        );
+        assert_eq!(f.nth_derivative(x, 0), f);
+    }
+}
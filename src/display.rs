@@ -0,0 +1,236 @@
+//! Infix rendering of terms, emitting only the parentheses that are required.
+//!
+//! For finite literals the output parses back into an equal [`FunctionTerm`].
+
+use std::fmt::{Display, Formatter, Result};
+
+use num_traits::Signed;
+
+use crate::{Constant, FunctionTerm, Operation, UnaryOperation, Value, NEGATION_PRECEDENCE};
+
+/// Precedence of an operand that never needs parentheses.
+const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+trait Precedence {
+    fn precedence(&self) -> u8;
+}
+
+fn literal_precedence(x: f64) -> u8 {
+    if x.is_sign_negative() {
+        NEGATION_PRECEDENCE
+    } else {
+        ATOM_PRECEDENCE
+    }
+}
+
+impl Precedence for FunctionTerm {
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Unary {
+                operation: UnaryOperation::Negate,
+                ..
+            } => NEGATION_PRECEDENCE,
+            Self::Variable(_) | Self::Unary { .. } => ATOM_PRECEDENCE,
+            Self::Value(x) => x.precedence(),
+            Self::Calculation { operation, .. } => operation.precedence(),
+        }
+    }
+}
+
+impl Precedence for Value {
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Literal(x) => literal_precedence(*x),
+            Self::Constant(_) => ATOM_PRECEDENCE,
+            // `1/3` is written as a division.
+            Self::Rational(x) if !x.is_integer() => Operation::Divide.precedence(),
+            Self::Rational(x) if x.numerator().is_negative() => NEGATION_PRECEDENCE,
+            Self::Rational(_) => ATOM_PRECEDENCE,
+            Self::_Calculation { operation, .. } => operation.precedence(),
+        }
+    }
+}
+
+fn write_operand<T: Display + Precedence>(
+    f: &mut Formatter<'_>,
+    operand: &T,
+    parent: Operation,
+    is_right: bool,
+) -> Result {
+    let precedence = operand.precedence();
+    let needs_parentheses = precedence < parent.precedence()
+        || (precedence == parent.precedence() && is_right != parent.is_right_associative());
+
+    if needs_parentheses {
+        write!(f, "({operand})")
+    } else {
+        write!(f, "{operand}")
+    }
+}
+
+fn write_calculation<T: Display + Precedence>(
+    f: &mut Formatter<'_>,
+    left: &T,
+    right: &T,
+    operation: Operation,
+) -> Result {
+    write_operand(f, left, operation, false)?;
+    match operation {
+        Operation::Pow => write!(f, "{operation}")?,
+        _ => write!(f, " {operation} ")?,
+    }
+    write_operand(f, right, operation, true)
+}
+
+fn write_unary(f: &mut Formatter<'_>, term: &FunctionTerm, operation: UnaryOperation) -> Result {
+    match operation {
+        // A non-negative literal would be parsed as a negative literal, so `-(2)` keeps its parentheses.
+        UnaryOperation::Negate
+            if term.precedence() < NEGATION_PRECEDENCE
+                || matches!(term, FunctionTerm::Value(Value::Literal(x)) if x.is_sign_positive()) =>
+        {
+            write!(f, "-({term})")
+        }
+        UnaryOperation::Negate => write!(f, "-{term}"),
+        UnaryOperation::Log(base) => write!(f, "log({base}, {term})"),
+        operation => write!(f, "{operation}({term})"),
+    }
+}
+
+impl Display for FunctionTerm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Variable(x) => write!(f, "{x}"),
+            Self::Value(x) => write!(f, "{x}"),
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => write_calculation(f, left.as_ref(), right.as_ref(), *operation),
+            Self::Unary { term, operation } => write_unary(f, term, *operation),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Literal(x) => write!(f, "{x}"),
+            Self::Constant(x) => write!(f, "{x}"),
+            Self::Rational(x) => write!(f, "{x}"),
+            Self::_Calculation {
+                left,
+                right,
+                operation,
+            } => write_calculation(f, left.as_ref(), right.as_ref(), *operation),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let symbol = match self {
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Pow => "^",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+impl Display for UnaryOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Constant, FunctionTerm, Identifier, Operation, UnaryOperation, Value};
+
+    fn assert_round_trip(term: &FunctionTerm) {
+        let printed = term.to_string();
+        let parsed: FunctionTerm = printed.parse().unwrap();
+        assert_eq!(&parsed, term, "{printed} did not parse back");
+    }
+
+    fn calculation(left: FunctionTerm, operation: Operation, right: FunctionTerm) -> FunctionTerm {
+        FunctionTerm::Calculation {
+            left: left.into(),
+            right: right.into(),
+            operation,
+        }
+    }
+
+    #[test]
+    fn parsed_terms_round_trip() {
+        for input in [
+            "1 + 2 * x",
+            "(1 + 2) * x",
+            "x - (y - z)",
+            "x - y - z",
+            "x / (y / z)",
+            "x^y^z",
+            "(x^y)^z",
+            "-x^2",
+            "(-x)^2",
+            "-2^2",
+            "(-2)^2",
+            "-(2)",
+            "--x",
+            "-(x + 1)",
+            "2 * -x",
+            "x - -3",
+            "sin(x)^2 + cos(x)^2",
+            "log(2, x) + log(x) + ln(x)",
+            "abs(-x) / sqrt(pi * e)",
+            "0.5 * tau - phi",
+            "exp(i * x)",
+            "floor(x_1) + ceil(y2)",
+        ] {
+            assert_round_trip(&input.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn built_terms_round_trip() {
+        let x = || FunctionTerm::Variable(Identifier::new("x"));
+        let literal = |value: f64| FunctionTerm::Value(Value::Literal(value));
+        let operations = [
+            Operation::Plus,
+            Operation::Minus,
+            Operation::Multiply,
+            Operation::Divide,
+            Operation::Pow,
+        ];
+        let operands = [
+            x(),
+            literal(2.),
+            literal(-2.),
+            literal(0.1),
+            FunctionTerm::Value(Value::Constant(Constant::Pi)),
+            -x(),
+            -literal(2.),
+            -literal(-2.),
+            x().unary(UnaryOperation::Log(0.5)),
+            calculation(x(), Operation::Plus, literal(1.)),
+            calculation(x(), Operation::Pow, literal(-1.)),
+        ];
+
+        for operation in operations {
+            for left in &operands {
+                for right in &operands {
+                    assert_round_trip(&calculation(left.clone(), operation, right.clone()));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Forward mode automatic differentiation with dual numbers.
+
+use anyhow::Result;
+
+use crate::{Function, MyError, Number, UnaryOperation};
+
+/// A value together with its partial derivatives with respect to every argument.
+///
+/// Missing trailing entries of the gradient are zero, so constants have an empty one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub gradient: Vec<f64>,
+}
+
+/// A product in which a zero derivative wins, so `0 * inf` at a singularity of an unrelated
+/// argument does not spoil the gradient.
+pub(crate) fn scaled(derivative: f64, factor: f64) -> f64 {
+    match derivative == 0. {
+        true => 0.,
+        false => derivative * factor,
+    }
+}
+
+impl Dual {
+    pub fn constant(value: f64) -> Dual {
+        Dual {
+            value,
+            gradient: Vec::new(),
+        }
+    }
+
+    /// The argument at `index` of `count` arguments.
+    pub(crate) fn variable(value: f64, index: usize, count: usize) -> Dual {
+        debug_assert!(index < count, "argument {index} of only {count}");
+        let mut gradient = vec![0.; count];
+        gradient[index] = 1.;
+        Dual { value, gradient }
+    }
+
+    /// The result of an operation with the given partial derivatives for both operands.
+    fn binary(&self, other: &Dual, value: f64, left: f64, right: f64) -> Dual {
+        let partial = |x: &Dual, i: usize| x.gradient.get(i).copied().unwrap_or(0.);
+        let gradient = (0..self.gradient.len().max(other.gradient.len()))
+            .map(|i| scaled(partial(self, i), left) + scaled(partial(other, i), right))
+            .collect();
+        Dual { value, gradient }
+    }
+
+    /// The result of a unary operation with the given derivative, by the chain rule.
+    fn chain(&self, value: f64, derivative: f64) -> Dual {
+        let gradient = self
+            .gradient
+            .iter()
+            .map(|&x| scaled(x, derivative))
+            .collect();
+        Dual { value, gradient }
+    }
+}
+
+impl Number for Dual {
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(Dual::constant(x))
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(self.binary(&other, self.value + other.value, 1., 1.))
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(self.binary(&other, self.value - other.value, 1., -1.))
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        let value = self.value * other.value;
+        Ok(self.binary(&other, value, other.value, self.value))
+    }
+
+    fn divide(self, other: Self) -> Result<Self> {
+        let value = self.value.divide(other.value)?;
+        Ok(self.binary(&other, value, 1. / other.value, -value / other.value))
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        let (x, y) = (self.value, exponent.value);
+        let value = x.pow(y)?;
+        // (x^y)' = y * x^(y - 1) * x' + x^y * ln(x) * y'
+        Ok(self.binary(&exponent, value, y * x.powf(y - 1.), value * x.ln()))
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        let x = self.value;
+        let value = x.unary(operation)?;
+
+        let derivative = match operation {
+            UnaryOperation::Negate => -1.,
+            UnaryOperation::Abs => x.unary(UnaryOperation::Sign)?,
+            UnaryOperation::Sqrt => 1. / (2. * value),
+            UnaryOperation::Exp => value,
+            UnaryOperation::Ln => 1. / x,
+            UnaryOperation::Log(base) => 1. / (x * base.ln()),
+            UnaryOperation::Sin => x.cos(),
+            UnaryOperation::Cos => -x.sin(),
+            UnaryOperation::Tan => 1. + value * value,
+            UnaryOperation::Asin => 1. / (1. - x * x).sqrt(),
+            UnaryOperation::Acos => -1. / (1. - x * x).sqrt(),
+            UnaryOperation::Atan => 1. / (1. + x * x),
+            UnaryOperation::Sinh => x.cosh(),
+            UnaryOperation::Cosh => x.sinh(),
+            UnaryOperation::Tanh => 1. - value * value,
+            UnaryOperation::Asinh => 1. / (x * x + 1.).sqrt(),
+            UnaryOperation::Acosh => 1. / (x * x - 1.).sqrt(),
+            UnaryOperation::Atanh => 1. / (1. - x * x),
+            // Piecewise constant.
+            UnaryOperation::Floor
+            | UnaryOperation::Ceil
+            | UnaryOperation::Round
+            | UnaryOperation::Sign => 0.,
+        };
+
+        Ok(self.chain(value, derivative))
+    }
+}
+
+impl Function {
+    /// The value and the partial derivatives with respect to every argument, in the order of
+    /// [`Function::arguments`], in a single evaluation.
+    pub fn eval_with_gradient(&self, args: &[f64]) -> Result<(f64, Vec<f64>)> {
+        let count = self.arguments.len();
+        if args.len() != count {
+            Err(MyError::ArityMismatch {
+                expected: count,
+                given: args.len(),
+            })?
+        }
+
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| Dual::variable(x, i, count))
+            .collect();
+        let Dual {
+            value,
+            mut gradient,
+        } = self.solve_args_in_order(args)?;
+        gradient.resize(count, 0.);
+        Ok((value, gradient))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, MyError};
+
+    /// Compares the gradient of `f` with its symbolic partial derivatives.
+    fn assert_gradient(f: &str, args: &[f64]) {
+        let f: Function = f.parse().unwrap();
+        let (value, gradient) = f.eval_with_gradient(args).unwrap();
+        assert_eq!(value, f.solve_args_in_order(args.to_vec()).unwrap());
+        assert_eq!(gradient.len(), f.arguments.len());
+
+        for (&x, actual) in f.arguments.iter().zip(gradient) {
+            let expected: f64 = f.derivative(x).solve_args_in_order(args.to_vec()).unwrap();
+            let error = (actual - expected).abs() / expected.abs().max(1.);
+            assert!(
+                error < 1e-12,
+                "d({})/d{x}: {actual} instead of {expected}",
+                f.term
+            );
+        }
+    }
+
+    #[test]
+    fn gradients_agree_with_derivatives() {
+        for (f, args) in [
+            ("x^2 * y + 3 * y", &[1.5, -2.][..]),
+            ("sin(x * y) / (1 + x^2)", &[0.3, 0.7]),
+            ("x^y", &[1.7, 2.5]),
+            ("exp(-x) * ln(y) - sqrt(x * y)", &[0.4, 2.2]),
+            ("log(2, x) + atan(y / x) + abs(x - y)", &[1.2, 0.5]),
+            (
+                "tanh(x) * asinh(y) + acosh(x + 1) * atanh(y / 2)",
+                &[0.8, 0.9],
+            ),
+            ("tan(x) + asin(y) - acos(y) * cosh(x)", &[0.2, -0.6]),
+            ("x * y * z - z^3", &[1., 2., 3.]),
+        ] {
+            assert_gradient(f, args);
+        }
+    }
+
+    #[test]
+    fn unused_arguments_have_zero_derivatives() {
+        let f: Function = "2 + y".parse().unwrap();
+        let f = Function {
+            arguments: vec![crate::Identifier::new("x"), f.arguments[0]],
+            term: f.term,
+        };
+        assert_eq!(f.eval_with_gradient(&[5., 1.]).unwrap(), (3., vec![0., 1.]));
+
+        // The singular derivative of `sqrt(y)` at 0 does not spoil the one with respect to `x`.
+        let f: Function = "x + sqrt(y)".parse().unwrap();
+        assert_eq!(
+            f.eval_with_gradient(&[1., 0.]).unwrap().1,
+            [1., f64::INFINITY]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let f: Function = "x / y".parse().unwrap();
+        let error: MyError = f.eval_with_gradient(&[1.]).unwrap_err().downcast().unwrap();
+        assert!(matches!(
+            error,
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        let error: MyError = f
+            .eval_with_gradient(&[1., 0.])
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(error, MyError::DivisionByZero));
+    }
+}
@@ -0,0 +1,108 @@
+//! Multiplying out products of sums.
+
+use crate::{Function, FunctionTerm, Operation, UnaryOperation};
+
+/// The highest power of a sum that is multiplied out, `(x + 1)^1000` is left alone.
+const MAX_EXPONENT: f64 = 64.;
+
+impl Function {
+    /// An equivalent function with every product of sums multiplied out.
+    pub fn expand(&self) -> Function {
+        Function {
+            arguments: self.arguments.clone(),
+            term: self.term.expand(),
+        }
+    }
+}
+
+impl FunctionTerm {
+    /// Distributes products over sums and multiplies out integer powers of sums, collecting
+    /// like terms, so `(x + 1)^2` becomes `x^2 + 2 * x + 1`.
+    pub fn expand(&self) -> FunctionTerm {
+        sum(self.expanded_summands()).simplify()
+    }
+
+    /// The summands of the expanded term, none of them a sum.
+    fn expanded_summands(&self) -> Vec<FunctionTerm> {
+        match self {
+            Self::Variable(_) | Self::Value(_) => vec![self.clone()],
+            Self::Unary {
+                term,
+                operation: UnaryOperation::Negate,
+            } => term.expanded_summands().into_iter().map(|x| -x).collect(),
+            Self::Unary { term, operation } => vec![term.expand().unary(*operation)],
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let left = left.expanded_summands();
+                match operation {
+                    Operation::Plus => [left, right.expanded_summands()].concat(),
+                    Operation::Minus => {
+                        let right = right.expanded_summands().into_iter().map(|x| -x);
+                        left.into_iter().chain(right).collect()
+                    }
+                    Operation::Multiply => multiply(&left, &right.expanded_summands()),
+                    // Only the numerator is distributed, `1 / (x + 1)` stays as it is.
+                    Operation::Divide => {
+                        let right = right.expand();
+                        left.into_iter().map(|x| x / right.clone()).collect()
+                    }
+                    Operation::Pow => {
+                        let exponent = right.expand();
+                        match exponent.literal() {
+                            Some(n)
+                                if left.len() > 1
+                                    && n.fract() == 0.
+                                    && (0. ..=MAX_EXPONENT).contains(&n) =>
+                            {
+                                (0..n as usize)
+                                    .fold(vec![1.0.into()], |power, _| multiply(&power, &left))
+                            }
+                            _ => vec![sum(left).simplify().pow(exponent)],
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The summands of an already simplified sum, with subtracted ones negated.
+    pub(crate) fn summands(&self) -> Vec<FunctionTerm> {
+        match self {
+            Self::Calculation {
+                left,
+                right,
+                operation: operation @ (Operation::Plus | Operation::Minus),
+            } => {
+                let mut summands = left.summands();
+                for x in right.summands() {
+                    summands.push(match operation {
+                        Operation::Minus => -x,
+                        _ => x,
+                    });
+                }
+                summands
+            }
+            term => vec![term.clone()],
+        }
+    }
+}
+
+fn sum(summands: Vec<FunctionTerm>) -> FunctionTerm {
+    summands
+        .into_iter()
+        .reduce(|x, y| x + y)
+        .unwrap_or(0.0.into())
+}
+
+/// Every product of one summand from each side, with like terms collected so that repeated
+/// multiplication does not grow exponentially.
+fn multiply(left: &[FunctionTerm], right: &[FunctionTerm]) -> Vec<FunctionTerm> {
+    let products = left
+        .iter()
+        .flat_map(|x| right.iter().map(move |y| x.clone() * y.clone()))
+        .collect();
+    sum(products).simplify().summands()
+}
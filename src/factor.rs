@@ -0,0 +1,504 @@
+//! Factoring: common factors of sums, and polynomials in one variable over the rationals.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    Complex, Function, FunctionTerm, Identifier, Number, Operation, Polynomial, Rational,
+    UnaryOperation,
+};
+
+/// The largest integer every smaller one of which is exact in an `f64`.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.;
+
+/// The most real roots and conjugate pairs whose products are all tried as factors.
+const MAX_UNITS: usize = 16;
+
+/// How close, relative to its size, a coefficient computed from roots must be to an integer.
+const INTEGER: f64 = 1e-6;
+
+impl Function {
+    /// An equivalent function written as a product of factors where possible.
+    pub fn factor(&self) -> Function {
+        Function {
+            arguments: self.arguments.clone(),
+            term: self.term.factor(),
+        }
+    }
+}
+
+impl FunctionTerm {
+    /// Writes a polynomial in one variable as a product of its content and its irreducible
+    /// factors over the rationals with their multiplicities, so `2 * x^2 - 2` becomes
+    /// `2 * (x + 1) * (x - 1)` and `x^4 + 4` becomes `(x^2 + 2 * x + 2) * (x^2 - 2 * x + 2)`.
+    /// Any other sum gets the factors common to all summands pulled out.
+    pub fn factor(&self) -> FunctionTerm {
+        let term = self.simplify();
+        if let [variable] = term.variables()[..] {
+            if let Ok(polynomial) = Polynomial::from_term(&term, variable) {
+                if let Some(factored) = factor_polynomial(&polynomial, variable) {
+                    return factored;
+                }
+            }
+        }
+        common_factor(&term)
+    }
+}
+
+/// Pulls the greatest common integer factor and the common powers out of a sum.
+fn common_factor(term: &FunctionTerm) -> FunctionTerm {
+    let summands: Vec<(f64, Vec<(FunctionTerm, f64)>)> =
+        term.summands().iter().map(|x| powers(x, false)).collect();
+    let Some((_, first)) = summands.first().filter(|_| summands.len() > 1) else {
+        return term.clone();
+    };
+
+    let integers = summands
+        .iter()
+        .all(|(c, _)| c.fract() == 0. && c.abs() <= MAX_EXACT);
+    let mut coefficient = match integers {
+        true => summands.iter().fold(0., |g, (c, _)| gcd(g, c.abs())),
+        false => 1.,
+    };
+    if summands.iter().all(|(c, _)| *c < 0.) {
+        coefficient = -coefficient;
+    }
+
+    let common: Vec<(FunctionTerm, f64)> = first
+        .iter()
+        .filter_map(|(base, _)| {
+            let exponents = summands.iter().map(|(_, factors)| {
+                factors
+                    .iter()
+                    .find(|(b, _)| b == base)
+                    .map_or(0., |(_, e)| *e)
+            });
+            let exponent = exponents.fold(f64::INFINITY, f64::min);
+            (exponent > 0.).then(|| (base.clone(), exponent))
+        })
+        .collect();
+    if coefficient == 1. && common.is_empty() {
+        return term.clone();
+    }
+
+    let rest = summands
+        .into_iter()
+        .map(|(c, factors)| {
+            let factors = factors.into_iter().map(|(base, e)| {
+                let pulled = common.iter().find(|(b, _)| *b == base).map_or(0., |x| x.1);
+                (base, e - pulled)
+            });
+            product(c / coefficient, factors)
+        })
+        .reduce(|x, y| x + y)
+        .unwrap_or(0.0.into())
+        .simplify()
+        .factor();
+    let rest = (rest, 1.);
+    product(coefficient, common.into_iter().chain([rest]))
+}
+
+/// A summand as its numeric coefficient and the bases it multiplies with their exponents, all
+/// inverted if `invert` is set.
+fn powers(term: &FunctionTerm, invert: bool) -> (f64, Vec<(FunctionTerm, f64)>) {
+    let sign = match invert {
+        true => -1.,
+        false => 1.,
+    };
+    if let Some(c) = term.literal() {
+        return (c.powf(sign), Vec::new());
+    }
+
+    match term {
+        FunctionTerm::Unary {
+            term,
+            operation: UnaryOperation::Negate,
+        } => {
+            let (c, factors) = powers(term, invert);
+            (-c, factors)
+        }
+        FunctionTerm::Calculation {
+            left,
+            right,
+            operation: operation @ (Operation::Multiply | Operation::Divide),
+        } => {
+            let (a, mut left) = powers(left, invert);
+            let (b, right) = powers(right, invert != (*operation == Operation::Divide));
+            for (base, e) in right {
+                match left.iter_mut().find(|(b, _)| *b == base) {
+                    Some((_, exponent)) => *exponent += e,
+                    None => left.push((base, e)),
+                }
+            }
+            (a * b, left)
+        }
+        FunctionTerm::Calculation {
+            left,
+            right,
+            operation: Operation::Pow,
+        } => match right.literal() {
+            Some(e) => (1., vec![(left.as_ref().clone(), sign * e)]),
+            None => (1., vec![(term.clone(), sign)]),
+        },
+        term => (1., vec![(term.clone(), sign)]),
+    }
+}
+
+/// `coefficient` times every base to its exponent, as a flat product.
+fn product(coefficient: f64, factors: impl Iterator<Item = (FunctionTerm, f64)>) -> FunctionTerm {
+    let powers = factors.filter(|(_, e)| *e != 0.).map(|(base, e)| match e {
+        1. => base,
+        e => base.pow(e.into()),
+    });
+    let magnitude = (coefficient.abs() != 1.).then(|| FunctionTerm::from(coefficient.abs()));
+    let term = magnitude
+        .into_iter()
+        .chain(powers)
+        .reduce(|x, y| x * y)
+        .unwrap_or(1.0.into());
+    match coefficient < 0. {
+        true => -term,
+        false => term,
+    }
+}
+
+fn gcd(a: f64, b: f64) -> f64 {
+    match b == 0. {
+        true => a,
+        false => gcd(b, a % b),
+    }
+}
+
+/// A polynomial with exact coefficients, lowest degree first and without trailing zeros.
+type Exact = Vec<BigRational>;
+
+fn trim(mut p: Exact) -> Exact {
+    while p.last().is_some_and(Zero::is_zero) {
+        p.pop();
+    }
+    p
+}
+
+fn derivative(p: &Exact) -> Exact {
+    let terms = p.iter().enumerate().skip(1);
+    trim(
+        terms
+            .map(|(i, c)| c * BigRational::from_integer(i.into()))
+            .collect(),
+    )
+}
+
+fn subtract(a: &Exact, b: &Exact) -> Exact {
+    let zero = BigRational::zero();
+    let difference = (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&zero) - b.get(i).unwrap_or(&zero))
+        .collect();
+    trim(difference)
+}
+
+/// Long division by a non-zero divisor.
+fn div_rem(a: &Exact, b: &Exact) -> (Exact, Exact) {
+    let mut remainder = a.clone();
+    let Some(shift) = a.len().checked_sub(b.len()) else {
+        return (Vec::new(), remainder);
+    };
+    let lead = &b[b.len() - 1];
+    let mut quotient = vec![BigRational::zero(); shift + 1];
+    for i in (0..=shift).rev() {
+        let factor = &remainder[i + b.len() - 1] / lead;
+        for (j, c) in b.iter().enumerate() {
+            remainder[i + j] -= &factor * c;
+        }
+        quotient[i] = factor;
+    }
+    (trim(quotient), trim(remainder))
+}
+
+fn monic(p: Exact) -> Exact {
+    match p.last().cloned() {
+        Some(lead) => p.into_iter().map(|c| c / &lead).collect(),
+        None => p,
+    }
+}
+
+fn polynomial_gcd(a: &Exact, b: &Exact) -> Exact {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_empty() {
+        let (_, remainder) = div_rem(&a, &b);
+        (a, b) = (b, remainder);
+    }
+    monic(a)
+}
+
+/// Splits `p` into a rational number and a polynomial with coprime integer coefficients and a
+/// positive leading one.
+fn primitive(p: &Exact) -> (BigRational, Exact) {
+    let denominators = p.iter().fold(BigInt::one(), |l, c| {
+        let d = c.denom();
+        &l / integer_gcd(&l, d) * d
+    });
+    let numerators: Vec<BigInt> = p
+        .iter()
+        .map(|c| (c * BigRational::from_integer(denominators.clone())).to_integer())
+        .collect();
+    let mut divisor = numerators
+        .iter()
+        .fold(BigInt::zero(), |g, c| integer_gcd(&g, c));
+    if numerators.last().is_some_and(Signed::is_negative) {
+        divisor = -divisor;
+    }
+    if divisor.is_zero() {
+        return (BigRational::one(), Vec::new());
+    }
+
+    let content = BigRational::new(divisor.clone(), denominators);
+    let p = numerators
+        .into_iter()
+        .map(|c| BigRational::from_integer(c / &divisor))
+        .collect();
+    (content, p)
+}
+
+fn integer_gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while !b.is_zero() {
+        (a, b) = (b.clone(), a % b);
+    }
+    a
+}
+
+/// Yun's algorithm over the rationals: the products of all roots of each multiplicity.
+fn square_free_factors(p: &Exact) -> Vec<(Exact, usize)> {
+    let p_prime = derivative(p);
+    let divisor = polynomial_gcd(p, &p_prime);
+    let mut b = div_rem(p, &divisor).0;
+    let mut d = subtract(&div_rem(&p_prime, &divisor).0, &derivative(&b));
+
+    let mut factors = Vec::new();
+    let mut multiplicity = 1;
+    while b.len() > 1 {
+        let factor = polynomial_gcd(&b, &d);
+        b = div_rem(&b, &factor).0;
+        d = subtract(&div_rem(&d, &factor).0, &derivative(&b));
+        if factor.len() > 1 {
+            factors.push((factor, multiplicity));
+        }
+        multiplicity += 1;
+    }
+    factors
+}
+
+/// The polynomial with the same, integer, coefficients, if they are all exact as `f64`.
+fn to_polynomial(p: &Exact) -> Option<Polynomial> {
+    let coefficients = p
+        .iter()
+        .map(|c| {
+            c.to_f64()
+                .filter(|x| c.is_integer() && x.abs() <= MAX_EXACT)
+        })
+        .collect::<Option<_>>()?;
+    Some(Polynomial::new(coefficients))
+}
+
+/// The irreducible factors with integer coefficients of a square-free polynomial with integer
+/// coefficients, smallest degree first.
+///
+/// Every factor is the leading coefficient's multiple of a product of roots, so the products of
+/// the numerical roots are tried, with conjugate pairs kept together, and those that round to a
+/// divisor are checked exactly.
+fn irreducible_factors(mut p: Exact) -> Vec<Exact> {
+    let roots = to_polynomial(&p)
+        .and_then(|x| x.roots().ok())
+        .unwrap_or_default();
+    // A root without its conjugate is off the real axis only by rounding.
+    let real = |z: Complex| {
+        z.im == 0.
+            || !roots
+                .iter()
+                .any(|x| x.value != z && x.value.sub(z.conj()).norm() <= INTEGER * z.norm())
+    };
+    let mut units: Vec<Polynomial> = roots
+        .iter()
+        .filter(|x| x.value.im >= 0. || real(x.value))
+        .map(|x| match real(x.value) {
+            true => Polynomial::new(vec![-x.value.re, 1.]),
+            false => Polynomial::new(vec![x.value.norm().powi(2), -2. * x.value.re, 1.]),
+        })
+        .collect();
+    let degree =
+        |units: &[Polynomial]| -> usize { units.iter().map(|x| x.degree().unwrap_or(0)).sum() };
+    if degree(&units) + 1 != p.len() {
+        return vec![p];
+    }
+
+    let mut factors = Vec::new();
+    while let Some((factor, used)) = smallest_factor(&p, &units) {
+        p = div_rem(&p, &factor).0;
+        factors.push(factor);
+        units = (0..units.len())
+            .filter(|i| used & (1 << i) == 0)
+            .map(|i| units[i].clone())
+            .collect();
+    }
+    if p.len() > 1 {
+        factors.push(p);
+    }
+    factors
+}
+
+/// The factor of smallest degree that is a product of some of `units`, with the set of those
+/// as a bit mask, or `None` if `p` is irreducible or has too many roots to search.
+fn smallest_factor(p: &Exact, units: &[Polynomial]) -> Option<(Exact, u32)> {
+    let half = (p.len() - 1) / 2;
+    let masks: Vec<u32> = match units.len() {
+        n if n <= MAX_UNITS => (1..1 << n).collect(),
+        // Only the linear and quadratic factors of the single roots.
+        n => (0..n).map(|i| 1 << i).collect(),
+    };
+    let lead = p.last()?.to_f64()?;
+
+    let chosen = |mask: u32| (0..units.len()).filter(move |i| mask & (1 << i) != 0);
+    for target in 1..=half {
+        for &mask in &masks {
+            let degree: usize = chosen(mask).map(|i| units[i].degree().unwrap_or(0)).sum();
+            if degree != target {
+                continue;
+            }
+            let product = chosen(mask).fold(Polynomial::constant(lead), |x, i| &x * &units[i]);
+            let coefficients = product
+                .coefficients
+                .iter()
+                .map(
+                    |&c| match (c - c.round()).abs() <= INTEGER * c.abs().max(1.) {
+                        true => BigInt::from_f64(c.round()).map(BigRational::from_integer),
+                        false => None,
+                    },
+                )
+                .collect::<Option<Exact>>();
+            let Some(candidate) = coefficients else {
+                continue;
+            };
+            let (_, factor) = primitive(&candidate);
+            if factor.len() > 1 && div_rem(p, &factor).1.is_empty() {
+                return Some((factor, mask));
+            }
+        }
+    }
+    None
+}
+
+fn factor_polynomial(polynomial: &Polynomial, variable: Identifier) -> Option<FunctionTerm> {
+    if polynomial.degree()? == 0 {
+        return None;
+    }
+    let exact: Exact = polynomial
+        .coefficients
+        .iter()
+        .map(|&c| Rational::from_f64(c).ok().map(|x| x.0))
+        .collect::<Option<_>>()?;
+
+    let (_, p) = primitive(&exact);
+    let mut factors = Vec::new();
+    for (factor, multiplicity) in square_free_factors(&p) {
+        let (_, factor) = primitive(&factor);
+        factors.extend(
+            irreducible_factors(factor)
+                .into_iter()
+                .map(|x| (x, multiplicity)),
+        );
+    }
+
+    // All factors are primitive, so what is left is the ratio of the leading coefficients.
+    let mut content = exact.last()?.clone();
+    let mut terms = Vec::new();
+    for (factor, multiplicity) in factors {
+        content /= num_traits::pow(factor.last()?.clone(), multiplicity);
+        let term = to_polynomial(&factor)?.to_term(variable);
+        terms.push(match multiplicity {
+            1 => term,
+            m => term.pow((m as f64).into()),
+        });
+    }
+
+    let magnitude = content.abs();
+    let magnitude = match magnitude.to_f64() {
+        _ if magnitude.is_one() => None,
+        Some(c) if magnitude.is_integer() && c <= MAX_EXACT => Some(FunctionTerm::from(c)),
+        _ => Some(FunctionTerm::from(Rational(magnitude))),
+    };
+    let product = magnitude.into_iter().chain(terms).reduce(|x, y| x * y)?;
+    Some(match content.is_negative() {
+        true => -product,
+        false => product,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, FunctionTerm};
+
+    fn factor(input: &str) -> String {
+        let term: FunctionTerm = input.parse().unwrap();
+        term.factor().to_string()
+    }
+
+    /// The expanded product of `(x - k)` for `k` from 1 to `n`.
+    fn wilkinson(n: i64) -> String {
+        let mut coefficients = vec![1i64];
+        for k in 1..=n {
+            let mut next = vec![0; coefficients.len() + 1];
+            for (i, c) in coefficients.iter().enumerate() {
+                next[i + 1] += c;
+                next[i] -= k * c;
+            }
+            coefficients = next;
+        }
+        coefficients
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("({c}) * x^{i}"))
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+
+    #[test]
+    fn polynomials() {
+        assert_eq!(factor("2 * x^2 - 2"), "2 * (x + 1) * (x - 1)");
+        assert_eq!(factor("6 * x^2 + 5 * x + 1"), "(2 * x + 1) * (3 * x + 1)");
+        assert_eq!(factor("x^4 + 4"), "(x^2 + 2 * x + 2) * (x^2 - 2 * x + 2)");
+        assert_eq!(factor("x^2 + 1"), "x^2 + 1");
+        assert_eq!(factor("x^3 - 3 * x + 2"), "(x + 2) * (x - 1)^2");
+    }
+
+    #[test]
+    fn roots_of_unity() {
+        assert_eq!(factor("x^2 - 1"), "(x + 1) * (x - 1)");
+        assert_eq!(factor("x^3 - 1"), "(x - 1) * (x^2 + x + 1)");
+        assert_eq!(factor("x^4 - 1"), "(x + 1) * (x - 1) * (x^2 + 1)");
+        assert_eq!(
+            factor("x^6 - 1"),
+            "(x + 1) * (x - 1) * (x^2 + x + 1) * (x^2 - x + 1)"
+        );
+    }
+
+    #[test]
+    fn wilkinson_polynomials() {
+        for n in [12, 14] {
+            let factored = factor(&wilkinson(n));
+            let actual: Vec<&str> = factored.split(" * ").collect();
+            assert_eq!(actual.len(), n as usize, "{factored}");
+            for k in 1..=n {
+                let expected = format!("(x - {k})");
+                assert!(actual.contains(&expected.as_str()), "{factored}");
+            }
+        }
+    }
+
+    #[test]
+    fn common_factors() {
+        assert_eq!(factor("6 * x * y + 9 * x"), "3 * x * (2 * y + 3)");
+        let f: Function = "x * sin(y) + sin(y)".parse().unwrap();
+        assert_eq!(f.factor().arguments, f.arguments);
+    }
+}
@@ -0,0 +1,141 @@
+//! Interned variable names.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter, Result},
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{Constant, MyError, UnaryOperation};
+
+/// A variable name, cheap to copy, compare and hash.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identifier(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Identifier {
+    /// For names known to be valid, like those the parser accepted. Panics otherwise, so
+    /// names from outside the crate go through [`Identifier::try_new`].
+    pub(crate) fn new(name: &str) -> Identifier {
+        match Identifier::try_new(name) {
+            Ok(identifier) => identifier,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// Fails for names that would not be read back as this variable: anything but a letter
+    /// followed by letters, digits and underscores, and the names of constants and functions.
+    pub fn try_new(name: &str) -> std::result::Result<Identifier, MyError> {
+        let mut chars = name.chars();
+        let valid = chars.next().is_some_and(char::is_alphabetic)
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+            && Constant::from_name(name).is_none()
+            && UnaryOperation::from_name(name).is_none();
+        if !valid {
+            return Err(MyError::InvalidVariableName {
+                name: name.to_string(),
+            });
+        }
+
+        let mut interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(&id) = interner.ids.get(name) {
+            return Ok(Identifier(id));
+        }
+
+        // Interned names live for the rest of the program anyway.
+        let name: &'static str = Box::leak(name.into());
+        let id = interner.names.len() as u32;
+        interner.names.push(name);
+        interner.ids.insert(name, id);
+        Ok(Identifier(id))
+    }
+
+    pub fn name(self) -> &'static str {
+        let interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        interner.names[self.0 as usize]
+    }
+}
+
+impl TryFrom<&str> for Identifier {
+    type Error = MyError;
+
+    fn try_from(name: &str) -> std::result::Result<Self, MyError> {
+        Identifier::try_new(name)
+    }
+}
+
+impl TryFrom<char> for Identifier {
+    type Error = MyError;
+
+    fn try_from(name: char) -> std::result::Result<Self, MyError> {
+        Identifier::try_new(name.encode_utf8(&mut [0; 4]))
+    }
+}
+
+/// Identifiers are ordered by name, so argument lists come out alphabetically.
+impl Ord for Identifier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name().cmp(other.name())
+    }
+}
+
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Identifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Debug for Identifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Identifier({:?})", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{f, Identifier, MyError};
+
+    #[test]
+    fn reserved_names_are_not_variables() {
+        for name in ["e", "pi", "i", "sin", "log", "", "1x", "x y", "x-1"] {
+            assert!(Identifier::try_new(name).is_err(), "{name} was accepted");
+        }
+        assert!(Identifier::try_new("x_1").is_ok());
+        assert!(matches!(
+            Identifier::try_from('e'),
+            Err(MyError::InvalidVariableName { .. })
+        ));
+    }
+
+    #[test]
+    fn identifiers_are_interned() {
+        let x = Identifier::try_from("theta").unwrap();
+        assert_eq!(x, Identifier::try_from("theta").unwrap());
+        assert_ne!(x, Identifier::try_from('x').unwrap());
+        assert_eq!(x.name(), "theta");
+    }
+
+    #[test]
+    fn functions_reject_reserved_arguments() {
+        assert_eq!(f!('x', "y").unwrap().arguments.len(), 2);
+        assert!(f!('x', 'e').is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//! Numerical definite integration over one argument of a function.
+
+use anyhow::Result;
+
+use crate::{Function, Identifier, MyError, Program};
+
+/// The error both methods aim for, absolute for small integrals and relative for large ones.
+const TOLERANCE: f64 = 1e-10;
+
+/// How often adaptive Simpson may halve an interval.
+const MAX_DEPTH: usize = 50;
+
+/// How many intervals adaptive Simpson may halve in total.
+const MAX_STEPS: usize = 50_000;
+
+/// How many pieces Gauss–Kronrod may split the interval into.
+const MAX_SEGMENTS: usize = 1000;
+
+/// How narrow a piece Gauss–Kronrod may split, relative to the size of its ends.
+const RESOLUTION: f64 = 1e3 * f64::EPSILON;
+
+/// The nodes of the 15 point Kronrod rule on `[-1, 1]`, the odd ones are the 7 point Gauss rule.
+const KRONROD_NODES: [f64; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.,
+];
+
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_18,
+    0.140_653_259_715_525_92,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_83,
+];
+
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+/// The method [`Function::integrate`] uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quadrature {
+    /// Simpson's rule, halving every interval until both halves agree with the whole.
+    ///
+    /// Evaluates the ends of the interval, so it fails on integrable singularities there.
+    Simpson,
+    /// The 7 point Gauss and 15 point Kronrod rules, always splitting the piece with the largest
+    /// error. Never evaluates the ends of the interval.
+    GaussKronrod,
+}
+
+/// The estimate of a definite integral.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Integral {
+    pub value: f64,
+    /// An estimate of the absolute error of `value`.
+    pub error: f64,
+}
+
+impl Integral {
+    fn add(self, other: Integral) -> Integral {
+        Integral {
+            value: self.value + other.value,
+            error: self.error + other.error,
+        }
+    }
+
+    fn is_accurate(&self) -> bool {
+        self.value.is_finite() && self.error <= TOLERANCE * self.value.abs().max(1.)
+    }
+
+    fn no_convergence(&self, iterations: usize) -> MyError {
+        MyError::NoConvergence {
+            iterations,
+            estimate: self.value,
+        }
+    }
+}
+
+/// The substitution that maps a finite interval of `t` onto a part of the bounds of the integral.
+#[derive(Debug, Clone, Copy)]
+enum Substitution {
+    None,
+    /// `x = a + (1 - t) / t` for `t` in `[0, 1]`, which puts infinity at 0 where floating point
+    /// numbers are the densest.
+    Above(f64),
+    /// `x = b - (1 - t) / t` for `t` in `[0, 1]`.
+    Below(f64),
+}
+
+impl Substitution {
+    /// The parts of the bounds `a < b`, each with its substitution and interval of `t`.
+    ///
+    /// Infinite bounds get parts of their own, starting a unit from the finite part, so a
+    /// singularity at a finite bound stays where floating point numbers can get close to it.
+    fn parts(a: f64, b: f64) -> Vec<(Substitution, f64, f64)> {
+        match (a.is_finite(), b.is_finite()) {
+            (true, true) => vec![(Substitution::None, a, b)],
+            (true, false) => vec![
+                (Substitution::None, a, a + 1.),
+                (Substitution::Above(a + 1.), 0., 1.),
+            ],
+            (false, true) => vec![
+                (Substitution::Below(b - 1.), 0., 1.),
+                (Substitution::None, b - 1., b),
+            ],
+            (false, false) => vec![
+                (Substitution::Below(-1.), 0., 1.),
+                (Substitution::None, -1., 1.),
+                (Substitution::Above(1.), 0., 1.),
+            ],
+        }
+    }
+
+    /// `x` and `|dx / dt|` at `t`, nothing at an infinite end.
+    fn at(self, t: f64) -> Option<(f64, f64)> {
+        match self {
+            Substitution::None => Some((t, 1.)),
+            _ if t == 0. => None,
+            Substitution::Above(a) => Some((a + (1. - t) / t, 1. / (t * t))),
+            Substitution::Below(b) => Some((b - (1. - t) / t, 1. / (t * t))),
+        }
+    }
+}
+
+/// The function to integrate, with every other argument fixed.
+struct Integrand<'a> {
+    program: &'a Program,
+    args: Vec<f64>,
+    index: usize,
+    substitution: Substitution,
+    /// -1 when integrating from the larger bound to the smaller one.
+    sign: f64,
+    /// How many steps adaptive Simpson has taken.
+    steps: usize,
+    stack: Vec<f64>,
+}
+
+impl Integrand<'_> {
+    fn at(&mut self, t: f64) -> Result<f64> {
+        let Some((x, weight)) = self.substitution.at(t) else {
+            // The integrand has to vanish at infinity for the integral to exist.
+            return Ok(0.);
+        };
+        self.args[self.index] = x;
+        Ok(self.program.eval_with(&self.args, &mut self.stack)? * weight * self.sign)
+    }
+
+    fn simpson(&mut self, a: f64, b: f64) -> Result<Integral> {
+        let m = a + (b - a) / 2.;
+        let (fa, fm, fb) = (self.at(a)?, self.at(m)?, self.at(b)?);
+        let whole = (b - a) / 6. * (fa + 4. * fm + fb);
+        let tolerance = TOLERANCE * whole.abs().max(1.);
+        let integral = self.simpson_step([a, m, b], [fa, fm, fb], whole, tolerance, MAX_DEPTH)?;
+        // Every piece that met its share of the tolerance keeps the sum within it.
+        if integral.error <= tolerance {
+            return Ok(integral);
+        }
+        Err(integral.no_convergence(self.steps))?
+    }
+
+    /// One step of adaptive Simpson on `[a, b]` with its middle `m`, where `whole` is the
+    /// estimate for the entire interval.
+    fn simpson_step(
+        &mut self,
+        [a, m, b]: [f64; 3],
+        [fa, fm, fb]: [f64; 3],
+        whole: f64,
+        tolerance: f64,
+        depth: usize,
+    ) -> Result<Integral> {
+        self.steps += 1;
+        let (left_middle, right_middle) = (a + (m - a) / 2., m + (b - m) / 2.);
+        let (fl, fr) = (self.at(left_middle)?, self.at(right_middle)?);
+        let left = (m - a) / 6. * (fa + 4. * fl + fm);
+        let right = (b - m) / 6. * (fm + 4. * fr + fb);
+
+        // The halves are 15 times as accurate as the whole, so the difference estimates the error.
+        let delta = left + right - whole;
+        let exhausted = depth == 0 || self.steps >= MAX_STEPS || left_middle == m;
+        if exhausted || delta.abs() <= 15. * tolerance {
+            return Ok(Integral {
+                value: left + right + delta / 15.,
+                error: delta.abs() / 15.,
+            });
+        }
+
+        let left = self.simpson_step(
+            [a, left_middle, m],
+            [fa, fl, fm],
+            left,
+            tolerance / 2.,
+            depth - 1,
+        )?;
+        let right = self.simpson_step(
+            [m, right_middle, b],
+            [fm, fr, fb],
+            right,
+            tolerance / 2.,
+            depth - 1,
+        )?;
+        Ok(left.add(right))
+    }
+
+    /// The Kronrod estimate on `[a, b]`, with its difference to the Gauss estimate as the error.
+    fn kronrod(&mut self, a: f64, b: f64) -> Result<Integral> {
+        let (center, half) = (a + (b - a) / 2., (b - a) / 2.);
+        let (mut kronrod, mut gauss) = (0., 0.);
+        for (i, (node, weight)) in KRONROD_NODES.iter().zip(KRONROD_WEIGHTS).enumerate() {
+            let y = match *node == 0. {
+                true => self.at(center)?,
+                false => self.at(center - half * node)? + self.at(center + half * node)?,
+            };
+            kronrod += weight * y;
+            if i % 2 == 1 {
+                gauss += GAUSS_WEIGHTS[i / 2] * y;
+            }
+        }
+
+        Ok(Integral {
+            value: kronrod * half,
+            error: ((kronrod - gauss) * half).abs(),
+        })
+    }
+
+    fn gauss_kronrod(&mut self, a: f64, b: f64) -> Result<Integral> {
+        let mut segments = vec![(a, b, self.kronrod(a, b)?)];
+        loop {
+            let total = segments
+                .iter()
+                .fold(Integral::default(), |total, &(_, _, x)| total.add(x));
+            if total.is_accurate() {
+                return Ok(total);
+            }
+            if segments.len() >= MAX_SEGMENTS {
+                Err(total.no_convergence(segments.len() - 1))?
+            }
+
+            let worst = (0..segments.len())
+                .max_by(|&i, &j| segments[i].2.error.total_cmp(&segments[j].2.error))
+                .unwrap_or_default();
+            let (a, b, _) = segments[worst];
+            // The nodes of a piece only a few units of rounding wide no longer tell the rules apart.
+            if b - a <= RESOLUTION * a.abs().max(b.abs()) {
+                Err(total.no_convergence(segments.len() - 1))?
+            }
+            let m = a + (b - a) / 2.;
+            segments[worst] = (a, m, self.kronrod(a, m)?);
+            segments.push((m, b, self.kronrod(m, b)?));
+        }
+    }
+}
+
+impl Function {
+    /// The integral over `var` from `a` to `b`, where either bound may be infinite.
+    ///
+    /// `fixed` holds the values of the other arguments, in the order of [`Function::arguments`].
+    /// Fails with [`MyError::NoConvergence`] and the best estimate found when the error cannot be
+    /// brought within the tolerance, as for most integrals that do not exist.
+    pub fn integrate(
+        &self,
+        var: Identifier,
+        a: f64,
+        b: f64,
+        fixed: &[f64],
+        method: Quadrature,
+    ) -> Result<Integral> {
+        let index = self
+            .arguments
+            .iter()
+            .position(|&x| x == var)
+            .ok_or(MyError::NoSuchVariable { variable: var })?;
+        if fixed.len() + 1 != self.arguments.len() {
+            Err(MyError::ArityMismatch {
+                expected: self.arguments.len() - 1,
+                given: fixed.len(),
+            })?
+        }
+        if a == b {
+            return Ok(Integral::default());
+        }
+
+        let program = self.compile()?;
+        let mut args = fixed.to_vec();
+        args.insert(index, 0.);
+        let mut integrand = Integrand {
+            program: &program,
+            args,
+            index,
+            substitution: Substitution::None,
+            sign: match a > b {
+                true => -1.,
+                false => 1.,
+            },
+            steps: 0,
+            stack: Vec::with_capacity(program.stack_size),
+        };
+
+        let mut total = Integral::default();
+        for (substitution, from, to) in Substitution::parts(a.min(b), a.max(b)) {
+            integrand.substitution = substitution;
+            total = total.add(match method {
+                Quadrature::Simpson => integrand.simpson(from, to)?,
+                Quadrature::GaussKronrod => integrand.gauss_kronrod(from, to)?,
+            });
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{Function, Identifier, MyError};
+
+    use super::Quadrature;
+
+    const METHODS: [Quadrature; 2] = [Quadrature::Simpson, Quadrature::GaussKronrod];
+
+    fn integrate(f: &str, a: f64, b: f64, method: Quadrature) -> anyhow::Result<f64> {
+        let f: Function = f.parse().unwrap();
+        Ok(f.integrate(f.arguments[0], a, b, &[], method)?.value)
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64, what: &str) {
+        let error = (actual - expected).abs() / expected.abs().max(1.);
+        assert!(error <= tolerance, "{what}: {actual} instead of {expected}");
+    }
+
+    #[test]
+    fn polynomials_are_exact() {
+        for method in METHODS {
+            for (f, a, b, expected) in [
+                ("3 + 0 * x", -1., 2., 9.),
+                ("x^3 - 2 * x", -1., 2., 0.75),
+                ("x^2", 3., 1., -26. / 3.),
+                ("x^2", 1., 1., 0.),
+            ] {
+                let actual = integrate(f, a, b, method).unwrap();
+                assert_close(actual, expected, 1e-15, f);
+            }
+        }
+        let actual = integrate("x^9 - x^4", 0., 2., Quadrature::GaussKronrod).unwrap();
+        assert_close(actual, 102.4 - 6.4, 1e-15, "x^9");
+    }
+
+    #[test]
+    fn smooth_and_infinite() {
+        for method in METHODS {
+            for (f, a, b, expected) in [
+                ("sin(x)", 0., PI, 2.),
+                ("exp(x)", 0., 1., 1f64.exp() - 1.),
+                ("1 / x^2", 1., f64::INFINITY, 1.),
+                ("1 / x^2", f64::INFINITY, 1., -1.),
+                ("exp(x)", f64::NEG_INFINITY, 0., 1.),
+                ("exp(-x^2)", f64::NEG_INFINITY, f64::INFINITY, PI.sqrt()),
+                ("1 / (1 + x^2)", f64::NEG_INFINITY, f64::INFINITY, PI),
+            ] {
+                let actual = integrate(f, a, b, method).unwrap();
+                assert_close(actual, expected, 1e-9, &format!("{f} by {method:?}"));
+            }
+        }
+    }
+
+    #[test]
+    fn singular_ends() {
+        // Only Gauss–Kronrod leaves out the ends, where these are undefined.
+        for (f, b, expected) in [
+            ("1 / sqrt(x)", 1., 2.),
+            ("ln(x)", 1., -1.),
+            ("exp(-x) / sqrt(x)", f64::INFINITY, PI.sqrt()),
+            // Decays slowly, so most of the integral is far out.
+            ("1 / (x + 1)^1.5", f64::INFINITY, 2.),
+        ] {
+            let actual = integrate(f, 0., b, Quadrature::GaussKronrod).unwrap();
+            assert_close(actual, expected, 1e-9, f);
+        }
+        let error: MyError = integrate("1 / sqrt(x)", 0., 1., Quadrature::Simpson)
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(error, MyError::DivisionByZero));
+    }
+
+    #[test]
+    fn fixed_arguments() {
+        let f: Function = "x * y^2 + y".parse().unwrap();
+        let y = Identifier::new("y");
+        for method in METHODS {
+            let integral = f.integrate(y, 0., 3., &[2.], method).unwrap();
+            assert_close(integral.value, 22.5, 1e-15, "x * y^2 + y");
+            assert!(integral.error <= 1e-10);
+        }
+
+        let error =
+            |result: anyhow::Result<_>| -> MyError { result.unwrap_err().downcast().unwrap() };
+        assert!(matches!(
+            error(f.integrate(y, 0., 1., &[], Quadrature::Simpson)),
+            MyError::ArityMismatch {
+                expected: 1,
+                given: 0
+            }
+        ));
+        assert!(matches!(
+            error(f.integrate(Identifier::new("z"), 0., 1., &[1.], Quadrature::Simpson)),
+            MyError::NoSuchVariable { .. }
+        ));
+    }
+
+    #[test]
+    fn divergent_integrals_fail() {
+        for method in METHODS {
+            for (f, a, b) in [
+                ("1 / x", 1., f64::INFINITY),
+                ("x", 0., f64::INFINITY),
+                ("sin(x)", 0., f64::INFINITY),
+            ] {
+                let error: MyError = integrate(f, a, b, method).unwrap_err().downcast().unwrap();
+                assert!(
+                    matches!(error, MyError::NoConvergence { .. }),
+                    "{f} by {method:?}: {error}"
+                );
+            }
+        }
+        let error: MyError = integrate("1 / x", 0., 1., Quadrature::GaussKronrod)
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(error, MyError::NoConvergence { .. }));
+    }
+}
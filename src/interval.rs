@@ -0,0 +1,392 @@
+//! Interval arithmetic, evaluating a term to bounds that are guaranteed to contain every result.
+
+use std::{
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    fmt::{Display, Formatter},
+};
+
+use anyhow::Result;
+
+use crate::{Constant, MyError, Number, Rational, UnaryOperation};
+
+/// All numbers from `lo` to `hi`, both inclusive. Either end may be infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+/// Rounds a computed lower bound down, treating an undefined one like `inf - inf` as unbounded.
+fn down(x: f64) -> f64 {
+    match x.is_nan() {
+        true => f64::NEG_INFINITY,
+        false => x.next_down(),
+    }
+}
+
+/// Rounds a computed upper bound up, treating an undefined one as unbounded.
+fn up(x: f64) -> f64 {
+    match x.is_nan() {
+        true => f64::INFINITY,
+        false => x.next_up(),
+    }
+}
+
+/// A product in which zero wins over infinity, as the zero is exact.
+fn product(x: f64, y: f64) -> f64 {
+    match x == 0. || y == 0. {
+        true => 0.,
+        false => x * y,
+    }
+}
+
+/// Bounds `x^n` for `x >= 0` by repeated squaring, rounding outward after every multiplication,
+/// as `f64::powi` may be off by more than one rounding.
+fn power_bounds(x: f64, n: u32) -> (f64, f64) {
+    let (mut base, mut n) = ((x, x), n);
+    let mut result = (1., 1.);
+    while n > 0 {
+        if n % 2 == 1 {
+            result = (down(result.0 * base.0).max(0.), up(result.1 * base.1));
+        }
+        base = (down(base.0 * base.0).max(0.), up(base.1 * base.1));
+        n /= 2;
+    }
+    result
+}
+
+/// Whether some `offset + k * period` lies in `[lo, hi]`, erring towards yes near the ends.
+fn contains_periodic(lo: f64, hi: f64, offset: f64, period: f64) -> bool {
+    let slack = 1e-12 * lo.abs().max(hi.abs()).max(1.);
+    let k = ((lo - slack - offset) / period).ceil();
+    offset + k * period <= hi + slack
+}
+
+impl Interval {
+    /// An interval from `lo` to `hi`, where `lo` must not be greater than `hi`.
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        Interval { lo, hi }
+    }
+
+    pub fn point(x: f64) -> Interval {
+        Interval::new(x, x)
+    }
+
+    pub fn entire() -> Interval {
+        Interval::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    /// The smallest interval containing both.
+    pub fn hull(&self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    fn increasing(self, f: impl Fn(f64) -> f64) -> Interval {
+        Interval::new(down(f(self.lo)), up(f(self.hi)))
+    }
+
+    fn decreasing(self, f: impl Fn(f64) -> f64) -> Interval {
+        Interval::new(down(f(self.hi)), up(f(self.lo)))
+    }
+
+    /// The part inside `[lo, hi]`, failing with `error` if there is none.
+    fn clip(self, lo: f64, hi: f64, error: impl FnOnce() -> MyError) -> Result<Interval> {
+        if self.hi < lo || self.lo > hi {
+            Err(error())?
+        }
+        Ok(Interval::new(self.lo.max(lo), self.hi.min(hi)))
+    }
+
+    /// Bounds sine or cosine, given where the function has its maxima.
+    fn periodic(self, f: impl Fn(f64) -> f64, maximum: f64) -> Interval {
+        if self.width() >= TAU {
+            return Interval::new(-1., 1.);
+        }
+        let (a, b) = (f(self.lo), f(self.hi));
+        let lo = match contains_periodic(self.lo, self.hi, maximum + PI, TAU) {
+            true => -1.,
+            false => down(a.min(b)),
+        };
+        let hi = match contains_periodic(self.lo, self.hi, maximum, TAU) {
+            true => 1.,
+            false => up(a.max(b)),
+        };
+        Interval::new(lo.max(-1.), hi.min(1.))
+    }
+
+    /// Raises to a fixed integer power, which is defined for negative numbers as well.
+    fn powi(self, n: i32) -> Result<Interval> {
+        if n == 0 {
+            return Ok(Interval::point(1.));
+        }
+        let m = n.unsigned_abs();
+        let lower = |x: f64| power_bounds(x, m).0;
+        let upper = |x: f64| power_bounds(x, m).1;
+        let power = match n % 2 == 0 {
+            true if self.lo >= 0. => Interval::new(lower(self.lo), upper(self.hi)),
+            true if self.hi <= 0. => Interval::new(lower(-self.hi), upper(-self.lo)),
+            true => Interval::new(0., upper(-self.lo).max(upper(self.hi))),
+            // Odd powers keep the sign, so a negative end is bounded by its negated magnitude.
+            false => Interval::new(
+                match self.lo >= 0. {
+                    true => lower(self.lo),
+                    false => -upper(-self.lo),
+                },
+                match self.hi >= 0. {
+                    true => upper(self.hi),
+                    false => -lower(-self.hi),
+                },
+            ),
+        };
+        match n < 0 {
+            true => Interval::point(1.).divide(power),
+            false => Ok(power),
+        }
+    }
+
+    /// The smallest and largest of `x^y` over the corners, both rounded outward.
+    fn corners(x: Interval, y: Interval) -> Interval {
+        let powers = [
+            x.lo.powf(y.lo),
+            x.lo.powf(y.hi),
+            x.hi.powf(y.lo),
+            x.hi.powf(y.hi),
+        ];
+        let lo = powers.into_iter().fold(f64::INFINITY, f64::min);
+        let hi = powers.into_iter().fold(f64::NEG_INFINITY, f64::max);
+        Interval::new(down(lo), up(hi))
+    }
+}
+
+/// Ends that are very small or large are written with an exponent, as outward rounding of zero
+/// gives bounds like `-5e-324`.
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let end = |x: f64| match x.abs() {
+            magnitude if magnitude != 0. && !(1e-6..1e16).contains(&magnitude) => format!("{x:e}"),
+            _ => format!("{x}"),
+        };
+        write!(f, "[{}, {}]", end(self.lo), end(self.hi))
+    }
+}
+
+/// Every operation is rounded outward, so the exact result for any arguments inside the argument
+/// intervals lies inside the result. Operations only defined on part of an interval bound that part.
+impl Number for Interval {
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(Interval::point(x))
+    }
+
+    /// The constants are irrational, so their `f64` is widened to an enclosure.
+    fn from_constant(constant: Constant) -> Result<Self> {
+        Ok(Interval::point(f64::from_constant(constant)?).increasing(|x| x))
+    }
+
+    /// The closest `f64` may be off by a rounding, so it is widened as well.
+    fn from_rational(x: &Rational) -> Result<Self> {
+        Ok(Interval::point(x.to_f64()).increasing(|x| x))
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(Interval::new(
+            down(self.lo + other.lo),
+            up(self.hi + other.hi),
+        ))
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(Interval::new(
+            down(self.lo - other.hi),
+            up(self.hi - other.lo),
+        ))
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        let products = [
+            product(self.lo, other.lo),
+            product(self.lo, other.hi),
+            product(self.hi, other.lo),
+            product(self.hi, other.hi),
+        ];
+        let lo = products.into_iter().fold(f64::INFINITY, f64::min);
+        let hi = products.into_iter().fold(f64::NEG_INFINITY, f64::max);
+        Ok(Interval::new(down(lo), up(hi)))
+    }
+
+    /// Dividing by an interval containing zero gives an unbounded interval, only dividing by
+    /// exactly zero fails.
+    fn divide(self, other: Self) -> Result<Self> {
+        let (x, y) = (self, other);
+
+        if y.lo > 0. || y.hi < 0. {
+            let quotients = [x.lo / y.lo, x.lo / y.hi, x.hi / y.lo, x.hi / y.hi];
+            let lo = quotients.into_iter().fold(f64::INFINITY, f64::min);
+            let hi = quotients.into_iter().fold(f64::NEG_INFINITY, f64::max);
+            return Ok(Interval::new(down(lo), up(hi)));
+        }
+        if y.lo == 0. && y.hi == 0. {
+            Err(MyError::DivisionByZero)?
+        }
+        if x.contains(0.) || (y.lo < 0. && y.hi > 0.) {
+            return Ok(Interval::entire());
+        }
+
+        // The divisor has zero at one end, so the result is unbounded on one side.
+        Ok(match (x.hi < 0., y.lo == 0.) {
+            (true, true) => Interval::new(f64::NEG_INFINITY, up(x.hi / y.hi)),
+            (true, false) => Interval::new(down(x.hi / y.lo), f64::INFINITY),
+            (false, true) => Interval::new(down(x.lo / y.hi), f64::INFINITY),
+            (false, false) => Interval::new(f64::NEG_INFINITY, up(x.lo / y.lo)),
+        })
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        if exponent.lo == exponent.hi
+            && exponent.lo.fract() == 0.
+            && exponent.lo.abs() <= i32::MAX as f64
+        {
+            return self.powi(exponent.lo as i32);
+        }
+
+        // Negative bases only have real powers for integer exponents, where the magnitude is
+        // bounded by the power of the largest absolute value.
+        let has_integer = exponent.lo.ceil() <= exponent.hi;
+        let negative = match self.lo < 0. && has_integer {
+            true => {
+                let magnitude = Interval::new(0., -self.lo);
+                let bound = Interval::corners(magnitude, exponent).hi;
+                Some(Interval::new(-bound, bound))
+            }
+            false => None,
+        };
+        let positive = match self.hi >= 0. {
+            true => Some(Interval::corners(
+                Interval::new(self.lo.max(0.), self.hi),
+                exponent,
+            )),
+            false => None,
+        };
+
+        match (positive, negative) {
+            (Some(positive), Some(negative)) => Ok(positive.hull(negative)),
+            (Some(result), None) | (None, Some(result)) => Ok(result),
+            (None, None) => Err(MyError::NotReal {
+                expression: format!("{self}^{exponent}"),
+            })?,
+        }
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        let x = self;
+        let out_of_domain = || MyError::OutOfDomain {
+            operation,
+            value: x.to_string(),
+        };
+
+        let result = match operation {
+            UnaryOperation::Negate => Interval::new(-x.hi, -x.lo),
+            UnaryOperation::Abs if x.lo >= 0. => x,
+            UnaryOperation::Abs if x.hi <= 0. => Interval::new(-x.hi, -x.lo),
+            UnaryOperation::Abs => Interval::new(0., x.hi.max(-x.lo)),
+            UnaryOperation::Sqrt => x
+                .clip(0., f64::INFINITY, || MyError::RootOfNegative {
+                    value: x.hi,
+                })?
+                .increasing(f64::sqrt),
+            UnaryOperation::Exp => x.increasing(f64::exp),
+            UnaryOperation::Ln | UnaryOperation::Log(_) if x.hi <= 0. => {
+                Err(MyError::LogarithmOfNonPositive { value: x.hi })?
+            }
+            UnaryOperation::Log(base) if base <= 0. || base == 1. => {
+                Err(MyError::InvalidLogarithmBase { base })?
+            }
+            UnaryOperation::Ln => Interval::new(x.lo.max(0.), x.hi).increasing(f64::ln),
+            UnaryOperation::Log(base) if base > 1. => {
+                Interval::new(x.lo.max(0.), x.hi).increasing(|x| x.log(base))
+            }
+            UnaryOperation::Log(base) => {
+                Interval::new(x.lo.max(0.), x.hi).decreasing(|x| x.log(base))
+            }
+            UnaryOperation::Sin => x.periodic(f64::sin, FRAC_PI_2),
+            UnaryOperation::Cos => x.periodic(f64::cos, 0.),
+            UnaryOperation::Tan
+                if x.width() >= PI || contains_periodic(x.lo, x.hi, FRAC_PI_2, PI) =>
+            {
+                Interval::entire()
+            }
+            UnaryOperation::Tan => x.increasing(f64::tan),
+            UnaryOperation::Asin => x.clip(-1., 1., out_of_domain)?.increasing(f64::asin),
+            UnaryOperation::Acos => x.clip(-1., 1., out_of_domain)?.decreasing(f64::acos),
+            UnaryOperation::Atan => x.increasing(f64::atan),
+            UnaryOperation::Sinh => x.increasing(f64::sinh),
+            UnaryOperation::Cosh if x.lo >= 0. => x.increasing(f64::cosh),
+            UnaryOperation::Cosh if x.hi <= 0. => x.decreasing(f64::cosh),
+            UnaryOperation::Cosh => Interval::new(1., up(x.lo.cosh().max(x.hi.cosh()))),
+            UnaryOperation::Tanh => x.increasing(f64::tanh),
+            UnaryOperation::Asinh => x.increasing(f64::asinh),
+            UnaryOperation::Acosh => x
+                .clip(1., f64::INFINITY, out_of_domain)?
+                .increasing(f64::acosh),
+            UnaryOperation::Atanh if x.hi <= -1. || x.lo >= 1. => Err(out_of_domain())?,
+            UnaryOperation::Atanh => x.clip(-1., 1., out_of_domain)?.increasing(f64::atanh),
+            UnaryOperation::Floor => Interval::new(x.lo.floor(), x.hi.floor()),
+            UnaryOperation::Ceil => Interval::new(x.lo.ceil(), x.hi.ceil()),
+            UnaryOperation::Round => Interval::new(x.lo.round(), x.hi.round()),
+            UnaryOperation::Sign => Interval::new(x.lo.unary(operation)?, x.hi.unary(operation)?),
+        };
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::BigRational;
+
+    use crate::{Interval, Number, Rational};
+
+    /// Whether the exact `x^n` lies in the interval computed for it.
+    fn encloses_power(x: f64, n: i32) -> bool {
+        let interval = Interval::point(x).pow(Interval::point(n as f64)).unwrap();
+        let exact = |x: f64| Rational(BigRational::from_float(x).unwrap());
+        let power = exact(x).pow(Rational::from(n as i64)).unwrap();
+        exact(interval.lo) <= power && power <= exact(interval.hi)
+    }
+
+    #[test]
+    fn integer_powers_enclose_the_exact_result() {
+        for i in 0..2000 {
+            let x = 1. + 0.25 * i as f64 / 2000.;
+            for n in [2, 7, 60, -13] {
+                assert!(encloses_power(x, n), "{x}^{n}");
+            }
+        }
+        for i in 0..100 {
+            let x = 0.01 + 3. * i as f64 / 100.;
+            for n in [3, -2, -60] {
+                assert!(encloses_power(-x, n), "{}^{n}", -x);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_powers_of_intervals_keep_their_sign() {
+        let x = Interval::new(-2., 3.);
+        let square = x.pow(Interval::point(2.)).unwrap();
+        let cube = x.pow(Interval::point(3.)).unwrap();
+        assert!(square.lo == 0. && square.contains(9.) && !square.contains(9.1));
+        assert!(cube.contains(-8.) && cube.contains(27.) && !cube.contains(-8.1));
+    }
+}
@@ -0,0 +1,649 @@
+mod antiderivative;
+mod batch;
+mod compile;
+mod complex;
+mod derivative;
+mod display;
+mod dual;
+mod expand;
+mod factor;
+mod identifier;
+mod integrate;
+mod interval;
+mod number;
+mod parallel;
+mod parser;
+mod polynomial;
+mod polynomial_roots;
+mod rational;
+mod reverse;
+mod roots;
+mod simplify;
+mod substitute;
+
+pub use compile::Program;
+pub use complex::Complex;
+pub use dual::Dual;
+pub use identifier::Identifier;
+pub use integrate::{Integral, Quadrature};
+pub use interval::Interval;
+pub use number::Number;
+pub use parallel::Range;
+pub use polynomial::Polynomial;
+pub use polynomial_roots::Root;
+pub use rational::Rational;
+pub use roots::{Derivative, Tolerance};
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use anyhow::{Context, Result};
+
+/// A function of the given arguments, failing with [`MyError::InvalidVariableName`] for a name
+/// that cannot be a variable.
+#[macro_export]
+macro_rules! f {
+    ($($e:expr), *) => {
+        (|| -> ::std::result::Result<$crate::Function, $crate::MyError> {
+            Ok($crate::Function {
+                arguments: vec![$($crate::Identifier::try_from($e)?),*],
+                term: $crate::FunctionTerm::Value($crate::Value::Literal(0.)),
+            })
+        })()
+    };
+}
+
+#[macro_export]
+macro_rules! solve {
+    ($e:ident($($args:expr), *)) => {
+        $e.solve_args_in_order(vec![$($args),*])
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub arguments: Vec<Identifier>,
+    pub term: FunctionTerm,
+}
+
+impl Function {
+    pub fn solve_for<N: Number>(&self, args: &HashMap<Identifier, N>) -> Result<N> {
+        self.term.solve(args)
+    }
+
+    pub fn solve_args_in_order<N: Number>(&self, in_order: Vec<N>) -> Result<N> {
+        if in_order.len() != self.arguments.len() {
+            Err(MyError::ArityMismatch {
+                expected: self.arguments.len(),
+                given: in_order.len(),
+            })?
+        }
+        self.solve_for(
+            &self
+                .arguments
+                .iter()
+                .zip(in_order)
+                .map(|(x, y)| (*x, y))
+                .collect(),
+        )
+    }
+
+    pub fn variable(&self, name: Identifier) -> Result<Box<FunctionTerm>> {
+        if !self.arguments.contains(&name) {
+            Err(MyError::NoSuchVariable { variable: name })?
+        }
+        Ok(FunctionTerm::Variable(name).into())
+    }
+}
+
+impl From<u32> for Box<FunctionTerm> {
+    fn from(value: u32) -> Self {
+        FunctionTerm::Value(Value::Literal(value as f64)).into()
+    }
+}
+
+impl From<f64> for FunctionTerm {
+    fn from(value: f64) -> Self {
+        FunctionTerm::Value(Value::Literal(value))
+    }
+}
+
+macro_rules! impl_term_operation {
+    ($trait:ident, $method:ident, $operation:ident) => {
+        impl $trait for FunctionTerm {
+            type Output = FunctionTerm;
+
+            fn $method(self, rhs: FunctionTerm) -> FunctionTerm {
+                FunctionTerm::Calculation {
+                    left: self.into(),
+                    right: rhs.into(),
+                    operation: Operation::$operation,
+                }
+            }
+        }
+    };
+}
+
+impl_term_operation!(Add, add, Plus);
+impl_term_operation!(Sub, sub, Minus);
+impl_term_operation!(Mul, mul, Multiply);
+impl_term_operation!(Div, div, Divide);
+
+impl Neg for FunctionTerm {
+    type Output = FunctionTerm;
+
+    fn neg(self) -> FunctionTerm {
+        self.unary(UnaryOperation::Negate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionTerm {
+    Variable(Identifier),
+    Value(Value),
+    Calculation {
+        left: Box<FunctionTerm>,
+        right: Box<FunctionTerm>,
+        operation: Operation,
+    },
+    Unary {
+        term: Box<FunctionTerm>,
+        operation: UnaryOperation,
+    },
+}
+
+impl FunctionTerm {
+    pub fn solve<N: Number>(&self, args: &HashMap<Identifier, N>) -> Result<N> {
+        let result = match self {
+            Self::Value(x) => x.get()?,
+            Self::Variable(x) => args
+                .get(x)
+                .cloned()
+                .ok_or(MyError::MissingArgument { variable: *x })?,
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => operation.apply(
+                left.solve(args)
+                    .context("Failed to solve the left hand side.")?,
+                right
+                    .solve(args)
+                    .context("Failed to solve the right hand side.")?,
+            )?,
+            Self::Unary { term, operation } => operation.apply(term.solve(args)?)?,
+        };
+
+        Ok(result)
+    }
+
+    pub fn pow(self, exponent: FunctionTerm) -> FunctionTerm {
+        FunctionTerm::Calculation {
+            left: self.into(),
+            right: exponent.into(),
+            operation: Operation::Pow,
+        }
+    }
+
+    pub fn ln(self) -> FunctionTerm {
+        self.unary(UnaryOperation::Ln)
+    }
+
+    pub fn unary(self, operation: UnaryOperation) -> FunctionTerm {
+        FunctionTerm::Unary {
+            term: self.into(),
+            operation,
+        }
+    }
+
+    pub fn contains(&self, variable: Identifier) -> bool {
+        match self {
+            Self::Variable(x) => *x == variable,
+            Self::Value(_) => false,
+            Self::Calculation { left, right, .. } => {
+                left.contains(variable) || right.contains(variable)
+            }
+            Self::Unary { term, .. } => term.contains(variable),
+        }
+    }
+
+    /// All variables used in the term, sorted and without duplicates.
+    pub fn variables(&self) -> Vec<Identifier> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables.sort();
+        variables.dedup();
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<Identifier>) {
+        match self {
+            Self::Variable(x) => variables.push(*x),
+            Self::Value(_) => {}
+            Self::Calculation { left, right, .. } => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            Self::Unary { term, .. } => term.collect_variables(variables),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Literal(f64),
+    Constant(Constant),
+    /// An exact fraction, kept exact by backends that support it.
+    Rational(Rational),
+    _Calculation {
+        left: Box<Value>,
+        right: Box<Value>,
+        operation: Operation,
+    },
+}
+
+impl Value {
+    pub fn get<N: Number>(&self) -> Result<N> {
+        Ok(match self {
+            Self::Literal(x) => N::from_f64(*x)?,
+            Self::Constant(x) => N::from_constant(*x)?,
+            Self::Rational(x) => N::from_rational(x)?,
+            Self::_Calculation {
+                left,
+                right,
+                operation,
+            } => operation.apply(
+                left.get().context("Failed to solve the left hand side.")?,
+                right
+                    .get()
+                    .context("Failed to solve the right hand side.")?,
+            )?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant {
+    Pi,
+    E,
+    Tau,
+    Phi,
+    /// The imaginary unit, only defined when evaluating with complex numbers.
+    I,
+}
+
+impl Constant {
+    const ALL: [Constant; 5] = [Self::Pi, Self::E, Self::Tau, Self::Phi, Self::I];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pi => "pi",
+            Self::E => "e",
+            Self::Tau => "tau",
+            Self::Phi => "phi",
+            Self::I => "i",
+        }
+    }
+
+    /// The real value of the constant, `None` for the imaginary unit.
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            Self::Pi => Some(std::f64::consts::PI),
+            Self::E => Some(std::f64::consts::E),
+            Self::Tau => Some(std::f64::consts::TAU),
+            // The golden ratio, (1 + sqrt(5)) / 2.
+            Self::Phi => Some(1.618_033_988_749_895),
+            Self::I => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperation {
+    Negate,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log(f64),
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Floor,
+    Ceil,
+    Round,
+    Sign,
+}
+
+impl UnaryOperation {
+    /// Every operation that is written as a function call, `log` standing for base 10.
+    const NAMED: [UnaryOperation; 21] = [
+        Self::Abs,
+        Self::Sqrt,
+        Self::Exp,
+        Self::Ln,
+        Self::Log(10.),
+        Self::Sin,
+        Self::Cos,
+        Self::Tan,
+        Self::Asin,
+        Self::Acos,
+        Self::Atan,
+        Self::Sinh,
+        Self::Cosh,
+        Self::Tanh,
+        Self::Asinh,
+        Self::Acosh,
+        Self::Atanh,
+        Self::Floor,
+        Self::Ceil,
+        Self::Round,
+        Self::Sign,
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED.into_iter().find(|x| x.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Negate => "-",
+            Self::Abs => "abs",
+            Self::Sqrt => "sqrt",
+            Self::Exp => "exp",
+            Self::Ln => "ln",
+            Self::Log(_) => "log",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Tan => "tan",
+            Self::Asin => "asin",
+            Self::Acos => "acos",
+            Self::Atan => "atan",
+            Self::Sinh => "sinh",
+            Self::Cosh => "cosh",
+            Self::Tanh => "tanh",
+            Self::Asinh => "asinh",
+            Self::Acosh => "acosh",
+            Self::Atanh => "atanh",
+            Self::Floor => "floor",
+            Self::Ceil => "ceil",
+            Self::Round => "round",
+            Self::Sign => "sign",
+        }
+    }
+
+    pub fn apply<N: Number>(&self, x: N) -> Result<N> {
+        x.unary(*self)
+    }
+}
+
+#[derive(Debug)]
+pub enum MyError {
+    DivisionByZero,
+    NoSuchVariable {
+        variable: Identifier,
+    },
+    MissingArgument {
+        variable: Identifier,
+    },
+    ArityMismatch {
+        expected: usize,
+        given: usize,
+    },
+    ColumnLengthMismatch {
+        expected: usize,
+        given: usize,
+    },
+    GridTooLarge {
+        columns: usize,
+        rows: usize,
+    },
+    UnexpectedCharacter {
+        character: char,
+        position: usize,
+    },
+    InvalidNumber {
+        literal: String,
+        position: usize,
+    },
+    UnexpectedToken {
+        token: String,
+        position: usize,
+    },
+    UnclosedParenthesis {
+        position: usize,
+    },
+    TooDeeplyNested {
+        position: usize,
+    },
+    UnexpectedEnd,
+    RootOfNegative {
+        value: f64,
+    },
+    LogarithmOfNonPositive {
+        value: f64,
+    },
+    InvalidLogarithmBase {
+        base: f64,
+    },
+    OutOfDomain {
+        operation: UnaryOperation,
+        value: String,
+    },
+    NotReal {
+        expression: String,
+    },
+    Irrational {
+        expression: String,
+    },
+    ExponentTooLarge {
+        exponent: String,
+    },
+    NoSignChange {
+        a: f64,
+        b: f64,
+    },
+    NoConvergence {
+        iterations: usize,
+        estimate: f64,
+    },
+    ZeroDerivative {
+        x: f64,
+    },
+    CannotIntegrate {
+        term: String,
+    },
+    NotPolynomial {
+        term: String,
+        variable: Identifier,
+    },
+    ZeroPolynomial,
+    InvalidVariableName {
+        name: String,
+    },
+}
+
+impl Display for MyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "Cannot divide by zero!"),
+            Self::NoSuchVariable { variable } => write!(
+                f,
+                "The variable {variable} does not exist for this function."
+            ),
+            Self::MissingArgument { variable } => {
+                write!(f, "No value was given for the variable {variable}.")
+            }
+            Self::ArityMismatch { expected, given } => write!(
+                f,
+                "The function takes {expected} arguments, but {given} were given."
+            ),
+            Self::ColumnLengthMismatch { expected, given } => write!(
+                f,
+                "All columns must have {expected} values, but one has {given}."
+            ),
+            Self::GridTooLarge { columns, rows } => write!(
+                f,
+                "A grid of {columns} by {rows} points has too many points to evaluate."
+            ),
+            Self::UnexpectedCharacter {
+                character,
+                position,
+            } => write!(
+                f,
+                "Unexpected character '{character}' at position {position}."
+            ),
+            Self::InvalidNumber { literal, position } => {
+                write!(f, "Invalid number '{literal}' at position {position}.")
+            }
+            Self::UnexpectedToken { token, position } => {
+                write!(f, "Unexpected '{token}' at position {position}.")
+            }
+            Self::UnclosedParenthesis { position } => {
+                write!(f, "The parenthesis at position {position} is never closed.")
+            }
+            Self::TooDeeplyNested { position } => {
+                write!(
+                    f,
+                    "The expression is nested too deeply at position {position}."
+                )
+            }
+            Self::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            Self::RootOfNegative { value } => {
+                write!(
+                    f,
+                    "Cannot take the square root of the negative number {value}."
+                )
+            }
+            Self::LogarithmOfNonPositive { value } => {
+                write!(
+                    f,
+                    "The logarithm is only defined for positive numbers, not {value}."
+                )
+            }
+            Self::InvalidLogarithmBase { base } => {
+                write!(f, "{base} is not a valid base for a logarithm.")
+            }
+            Self::OutOfDomain { operation, value } => {
+                write!(f, "{operation} is not defined for {value}.")
+            }
+            Self::NotReal { expression } => write!(f, "{expression} is not a real number."),
+            Self::Irrational { expression } => {
+                write!(f, "{expression} is not a rational number.")
+            }
+            Self::ExponentTooLarge { exponent } => {
+                write!(
+                    f,
+                    "The exponent {exponent} is too large to compute exactly."
+                )
+            }
+            Self::NoSignChange { a, b } => write!(
+                f,
+                "The function has the same sign at {a} and {b}, so there is no root to bracket."
+            ),
+            Self::NoConvergence {
+                iterations,
+                estimate,
+            } => write!(
+                f,
+                "No result was accurate enough within {iterations} iterations, the last estimate was {estimate}."
+            ),
+            Self::ZeroDerivative { x } => {
+                write!(f, "The function is flat at {x}, so there is no next estimate.")
+            }
+            Self::CannotIntegrate { term } => {
+                write!(f, "No rule for an antiderivative of {term} is known.")
+            }
+            Self::NotPolynomial { term, variable } => {
+                write!(f, "{term} is not a polynomial in {variable}.")
+            }
+            Self::ZeroPolynomial => write!(f, "Every number is a root of the zero polynomial."),
+            Self::InvalidVariableName { name } => {
+                write!(f, "'{name}' cannot be used as the name of a variable.")
+            }
+        }
+    }
+}
+
+impl Error for MyError {}
+
+impl MyError {
+    /// Position in the parsed input the error refers to, if any.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Self::UnexpectedCharacter { position, .. }
+            | Self::InvalidNumber { position, .. }
+            | Self::UnexpectedToken { position, .. }
+            | Self::UnclosedParenthesis { position }
+            | Self::TooDeeplyNested { position } => Some(*position),
+            _ => None,
+        }
+    }
+
+    /// Moves the position forward by `offset`, for input parsed from within a larger text.
+    pub fn shifted(mut self, offset: usize) -> Self {
+        match &mut self {
+            Self::UnexpectedCharacter { position, .. }
+            | Self::InvalidNumber { position, .. }
+            | Self::UnexpectedToken { position, .. }
+            | Self::UnclosedParenthesis { position }
+            | Self::TooDeeplyNested { position } => *position += offset,
+            _ => {}
+        }
+        self
+    }
+}
+
+impl Operation {
+    /// Binding strength of the operation, higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Plus | Self::Minus => 1,
+            Self::Multiply | Self::Divide => 2,
+            Self::Pow => 4,
+        }
+    }
+
+    fn is_right_associative(&self) -> bool {
+        matches!(self, Self::Pow)
+    }
+
+    pub fn apply<N: Number>(&self, left: N, right: N) -> Result<N> {
+        match self {
+            Self::Plus => left.plus(right),
+            Self::Minus => left.minus(right),
+            Self::Multiply => left.multiply(right),
+            Self::Divide => left.divide(right),
+            Self::Pow => left.pow(right),
+        }
+    }
+}
+
+/// Unary minus binds tighter than `*` but looser than `^`, so `-x^2` is `-(x^2)`.
+const NEGATION_PRECEDENCE: u8 = 3;
@@ -1,173 +1,398 @@
-use std::{collections::HashMap, error::Error, fmt::Display};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
+use math::{Complex, Function, FunctionTerm, Interval, MyError, Number, Quadrature, Rational};
+use rustyline::{error::ReadlineError, DefaultEditor};
 
-macro_rules! f {
-    ($($e:expr), *) => {
-        Function {
-            arguments: vec![$($e)*],
-            term: FunctionTerm::Value(Value::Literal(0.)),
-        }
-    };
-}
+const PROMPT: &str = ">> ";
+
+const MAX_TABLE_ROWS: usize = 10_000;
 
-macro_rules! solve {
-    ($e:ident($($args:expr), *)) => {
-        $e.solve_args_in_order(vec![$($args)*])
-    };
+const HELP: &str = "\
+f(x, y) = x^2 + y   define a function
+f(3, 4)             evaluate a function
+2^10 + 1            evaluate an expression
+:exact 1/3 + 1/6    evaluate an expression with exact fractions
+:complex sqrt(-4)   evaluate an expression with complex numbers
+:list               list all defined functions
+:delete f           delete a function
+:table f 0 10 1     tabulate f from 0 to 10 in steps of 1, or down with 10 0 1
+:range f 0 1        bound f for every argument from 0 to 1, one pair of bounds per argument
+:roots f -5 5       find every root of f from -5 to 5
+:integrate f 0 inf  integrate f from 0 to infinity
+:compose h f g      define h as f applied to the result of g
+:help               show this help
+:quit               exit (or press Ctrl-D)";
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".math_history"))
 }
 
-struct Function {
-    arguments: Vec<char>,
-    term: FunctionTerm,
+/// Parses `text`, which starts at char `offset` of the line, so that errors point into the line.
+fn parse_at<T: FromStr<Err = anyhow::Error>>(text: &str, offset: usize) -> Result<T> {
+    text.parse()
+        .map_err(|error: anyhow::Error| match error.downcast::<MyError>() {
+            Ok(error) => error.shifted(offset).into(),
+            Err(error) => error,
+        })
 }
 
-impl Function {
-    fn solve_for(&self, args: &HashMap<char, f64>) -> Result<f64> {
-        self.term.solve(&args)
+/// Evaluates a term that must not contain any variables.
+fn evaluate<N: Number>(term: &FunctionTerm) -> Result<N> {
+    if let Some(&variable) = term.variables().first() {
+        Err(MyError::NoSuchVariable { variable })?
     }
+    term.solve(&HashMap::new())
+}
 
-    fn solve_args_in_order(&self, in_order: Vec<f64>) -> Result<f64> {
-        self.solve_for(
-            &self
-                .arguments
-                .iter()
-                .zip(in_order)
-                .map(|(x, y)| (*x, y))
-                .collect(),
-        )
-    }
+/// Evaluates the expression following a command like `:exact `, which starts at char `offset`
+/// of the line.
+fn evaluate_command<N: Number + Display>(expression: &str, offset: usize) -> Result<String> {
+    let term: FunctionTerm = parse_at(expression, offset)?;
+    Ok(evaluate::<N>(&term)?.to_string())
+}
 
-    fn variable(&self, name: char) -> Result<Box<FunctionTerm>> {
-        if !self.arguments.contains(&name) {
-            Err(MyError::NoSuchVariable { variable: name })?
-        }
-        Ok(FunctionTerm::Variable(name).into())
+/// Evaluates a bound that may also be `inf` or `-inf`.
+fn bound(text: &str) -> Result<f64> {
+    match text {
+        "inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        _ => evaluate(&text.parse()?),
     }
 }
 
-impl From<u32> for Box<FunctionTerm> {
-    fn from(value: u32) -> Self {
-        FunctionTerm::Value(Value::Literal(value as f64)).into()
-    }
+fn char_offset(line: &str, byte_offset: usize) -> usize {
+    line[..byte_offset].chars().count()
 }
 
-enum FunctionTerm {
-    Variable(char),
-    Value(Value),
-    Calculation {
-        left: Box<FunctionTerm>,
-        right: Box<FunctionTerm>,
-        operation: Operation,
-    },
+fn signature(name: &str, function: &Function) -> String {
+    let arguments: Vec<String> = function.arguments.iter().map(ToString::to_string).collect();
+    format!("{name}({}) = {}", arguments.join(", "), function.term)
 }
 
-impl FunctionTerm {
-    fn solve(&self, args: &HashMap<char, f64>) -> Result<f64> {
-        let result = match self {
-            Self::Value(x) => x.get()?,
-            Self::Variable(x) => args[&x],
-            Self::Calculation {
-                left,
-                right,
-                operation,
-            } => operation.apply(
-                left.solve(args)
-                    .context("Failed to solve the left hand side.")?,
-                right
-                    .solve(args)
-                    .context("Failed to solve the right hand side.")?,
-            )?,
-        };
+/// Splits `name(a, b)` into the name and the argument list with its byte offset.
+fn split_call(text: &str) -> Option<(&str, &str, usize)> {
+    let open = text.find('(')?;
+    let inner = text.strip_suffix(')')?.get(open + 1..)?;
+    Some((text[..open].trim(), inner, open + 1))
+}
 
-        Ok(result)
+/// Splits an argument list at the commas that are not inside parentheses, so that
+/// `log(2, 8), 1` is two arguments.
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut arguments = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                arguments.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
     }
+    arguments.push(&text[start..]);
+    arguments
 }
 
-enum Value {
-    Literal(f64),
-    _Calculation {
-        left: Box<Value>,
-        right: Box<Value>,
-        operation: Operation,
-    },
+#[derive(Default)]
+struct Repl {
+    functions: BTreeMap<String, Function>,
 }
 
-impl Value {
-    fn get(&self) -> Result<f64> {
-        Ok(match self {
-            Self::Literal(x) => *x,
-            Self::_Calculation {
-                left,
-                right,
-                operation,
-            } => operation.apply(
-                left.get().context("Failed to solve the left hand side.")?,
-                right
-                    .get()
-                    .context("Failed to solve the right hand side.")?,
-            )?,
-        })
+impl Repl {
+    /// Runs a single line of input, returning the text to print.
+    fn execute(&mut self, line: &str) -> Result<Option<String>> {
+        let input = line.trim_end();
+        let trimmed = input.trim_start();
+
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        if let Some(command) = trimmed.strip_prefix(':') {
+            let offset = char_offset(input, input.len() - command.len());
+            return self.command(command, offset);
+        }
+        if let Some(equals) = input.find('=') {
+            return self.define(input, equals).map(Some);
+        }
+        if let Some((name, arguments, offset)) = split_call(input) {
+            if self.functions.contains_key(name) {
+                let offset = char_offset(input, offset);
+                return self.call(name, arguments, offset).map(Some);
+            }
+        }
+
+        let term: FunctionTerm = parse_at(input, 0)?;
+        Ok(Some(evaluate::<f64>(&term)?.to_string()))
     }
-}
 
-enum Operation {
-    _Plus,
-    _Minus,
-    _Multiply,
-    _Divide,
-    Pow,
-}
+    /// Runs the command that starts at char `offset` of the line, just past the ':'.
+    fn command(&mut self, command: &str, offset: usize) -> Result<Option<String>> {
+        if let Some(expression) = command.strip_prefix("exact ") {
+            return evaluate_command::<Rational>(expression, offset + "exact ".len()).map(Some);
+        }
+        if let Some(expression) = command.strip_prefix("complex ") {
+            return evaluate_command::<Complex>(expression, offset + "complex ".len()).map(Some);
+        }
 
-#[derive(Debug)]
-enum MyError {
-    DivisionByZero,
-    NoSuchVariable { variable: char },
-}
+        let words: Vec<&str> = command.split_whitespace().collect();
 
-impl Display for MyError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::DivisionByZero => write!(f, "Cannot divide by zero!"),
-            Self::NoSuchVariable { variable } => write!(
-                f,
-                "The variable {variable} does not exist for this function."
-            ),
+        match words.as_slice() {
+            ["help"] => Ok(Some(HELP.to_string())),
+            ["list"] if self.functions.is_empty() => Ok(Some("No functions defined.".to_string())),
+            ["list"] => {
+                let definitions: Vec<String> = self
+                    .functions
+                    .iter()
+                    .map(|(name, function)| signature(name, function))
+                    .collect();
+                Ok(Some(definitions.join("\n")))
+            }
+            ["delete", name] => match self.functions.remove(*name) {
+                Some(function) => Ok(Some(format!("Deleted {}", signature(name, &function)))),
+                None => Err(anyhow!("There is no function named '{name}'.")),
+            },
+            ["table", name, from, to, step] => self.table(name, from, to, step).map(Some),
+            ["range", name, bounds @ ..] => self.range(name, bounds).map(Some),
+            ["roots", name, from, to] => self.roots(name, from, to).map(Some),
+            ["integrate", name, from, to] => self.integrate(name, from, to).map(Some),
+            ["compose", name, outer, inner] => self.compose(name, outer, inner).map(Some),
+            _ => Err(anyhow!("Unknown command ':{command}', try :help.")),
         }
     }
-}
 
-impl Error for MyError {}
-
-impl Operation {
-    fn apply(&self, left: f64, right: f64) -> Result<f64> {
-        let result = match self {
-            Self::_Plus => left + right,
-            Self::_Minus => left - right,
-            Self::_Multiply => left * right,
-            Self::_Divide => {
-                if right == 0. {
-                    Err(MyError::DivisionByZero)?
+    fn table(&self, name: &str, from: &str, to: &str, step: &str) -> Result<String> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no function named '{name}'."))?;
+        if function.arguments.len() != 1 {
+            Err(MyError::ArityMismatch {
+                expected: function.arguments.len(),
+                given: 1,
+            })?
+        }
+
+        let [from, to, step] = [from, to, step].map(|x| evaluate::<f64>(&x.parse()?));
+        let (from, to, step) = (from?, to?, step?);
+        if step.is_nan() || step <= 0. {
+            Err(anyhow!("The step must be positive."))?
+        }
+
+        // A range like `10 0` counts down.
+        let step = match to < from {
+            true => -step,
+            false => step,
+        };
+        let count = ((to - from) / step + 1e-9).floor() + 1.;
+        if !(0. ..=MAX_TABLE_ROWS as f64).contains(&count) {
+            Err(anyhow!("A table can have at most {MAX_TABLE_ROWS} rows."))?
+        }
+
+        let rows: Vec<[f64; 1]> = (0..count as usize)
+            .map(|i| [from + i as f64 * step])
+            .collect();
+        let lines: Vec<String> = rows
+            .iter()
+            .zip(function.eval_rows(&rows)?)
+            .map(|([x], y)| match y {
+                Ok(y) => format!("{name}({x}) = {y}"),
+                Err(error) => format!("{name}({x}): error: {}", error.root_cause()),
+            })
+            .collect();
+
+        Ok(lines.join("\n"))
+    }
+
+    fn range(&self, name: &str, bounds: &[&str]) -> Result<String> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no function named '{name}'."))?;
+        if !bounds.len().is_multiple_of(2) {
+            Err(anyhow!("Every argument needs a lower and an upper bound."))?
+        }
+
+        let mut intervals = Vec::new();
+        for pair in bounds.chunks(2) {
+            let lo = evaluate::<f64>(&pair[0].parse()?)?;
+            let hi = evaluate::<f64>(&pair[1].parse()?)?;
+            if lo.is_nan() || hi.is_nan() || lo > hi {
+                Err(anyhow!(
+                    "The lower bound {lo} is above the upper bound {hi}."
+                ))?
+            }
+            intervals.push(Interval::new(lo, hi));
+        }
+
+        Ok(function.solve_args_in_order(intervals)?.to_string())
+    }
+
+    fn roots(&self, name: &str, from: &str, to: &str) -> Result<String> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no function named '{name}'."))?;
+        let from = evaluate::<f64>(&from.parse()?)?;
+        let to = evaluate::<f64>(&to.parse()?)?;
+
+        let roots = function.roots_in(from, to)?;
+        if roots.is_empty() {
+            return Ok(format!("{name} has no roots from {from} to {to}."));
+        }
+        let lines: Vec<String> = roots.iter().map(|x| format!("{name}({x}) = 0")).collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn integrate(&self, name: &str, from: &str, to: &str) -> Result<String> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no function named '{name}'."))?;
+        if function.arguments.len() != 1 {
+            Err(MyError::ArityMismatch {
+                expected: function.arguments.len(),
+                given: 1,
+            })?
+        }
+
+        let (from, to) = (bound(from)?, bound(to)?);
+        let integral = function.integrate(
+            function.arguments[0],
+            from,
+            to,
+            &[],
+            Quadrature::GaussKronrod,
+        )?;
+        Ok(format!("{} ± {:e}", integral.value, integral.error))
+    }
+
+    fn compose(&mut self, name: &str, outer: &str, inner: &str) -> Result<String> {
+        if !name.chars().all(char::is_alphabetic) {
+            Err(anyhow!("Invalid function name '{name}'."))?
+        }
+        let [outer, inner] = [outer, inner].map(|x| {
+            self.functions
+                .get(x)
+                .ok_or_else(|| anyhow!("There is no function named '{x}'."))
+        });
+
+        let function = outer?.compose(inner?)?;
+        let definition = signature(name, &function);
+        self.functions.insert(name.to_string(), function);
+        Ok(definition)
+    }
+
+    fn define(&mut self, input: &str, equals: usize) -> Result<String> {
+        let head = input[..equals].trim_end();
+        let (name, parameters, offset) =
+            split_call(head).ok_or_else(|| anyhow!("Expected a definition like f(x) = x^2."))?;
+
+        if name.is_empty() || !name.chars().all(char::is_alphabetic) {
+            Err(anyhow!("Invalid function name '{name}'."))?
+        }
+
+        let mut arguments = Vec::new();
+        if !parameters.trim().is_empty() {
+            let mut start = char_offset(input, offset);
+            for parameter in split_arguments(parameters) {
+                match parse_at(parameter, start)? {
+                    FunctionTerm::Variable(x) if !arguments.contains(&x) => arguments.push(x),
+                    _ => Err(anyhow!(
+                        "Invalid argument '{}' for {name}.",
+                        parameter.trim()
+                    ))?,
                 }
-                left / right
+                start += parameter.chars().count() + 1;
             }
-            Self::Pow => left.powf(right),
-        };
+        }
 
-        Ok(result)
+        let body = &input[equals + 1..];
+        let term: FunctionTerm = parse_at(body, char_offset(input, equals + 1))?;
+        if let Some(&variable) = term.variables().iter().find(|x| !arguments.contains(x)) {
+            Err(MyError::NoSuchVariable { variable })?
+        }
+
+        let function = Function { arguments, term };
+        let definition = signature(name, &function);
+        self.functions.insert(name.to_string(), function);
+        Ok(definition)
+    }
+
+    fn call(&self, name: &str, arguments: &str, offset: usize) -> Result<String> {
+        let function = &self.functions[name];
+
+        let mut values = Vec::new();
+        if !arguments.trim().is_empty() {
+            let mut start = offset;
+            for argument in split_arguments(arguments) {
+                let term: FunctionTerm = parse_at(argument, start)?;
+                values.push(evaluate::<f64>(&term)?);
+                start += argument.chars().count() + 1;
+            }
+        }
+
+        Ok(function.solve_args_in_order(values)?.to_string())
     }
 }
 
-fn main() {
-    // f(x) = x²
-    let mut f = f!('x');
-    f.term = FunctionTerm::Calculation {
-        left: f.variable('x').unwrap(),
-        right: 2.into(),
-        operation: Operation::Pow,
-    };
+fn report(error: &anyhow::Error) {
+    if let Some(position) = error.downcast_ref::<MyError>().and_then(MyError::position) {
+        eprintln!("{}^", " ".repeat(PROMPT.len() + position));
+    }
+    eprintln!("error: {}", error.root_cause());
+}
 
-    for x in (0..=10).map(|x| x as f64) {
-        println!("f({x}) = {}", solve!(f(x)).unwrap())
+fn main() -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(history) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(history);
     }
+
+    println!("Type :help for help.");
+    let mut repl = Repl::default();
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => Err(error)?,
+        };
+
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str())?;
+        }
+
+        if line.trim() == ":quit" {
+            break;
+        }
+
+        match repl.execute(&line) {
+            Ok(Some(output)) => println!("{output}"),
+            Ok(None) => {}
+            Err(error) => report(&error),
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(error) = editor.save_history(history) {
+            eprintln!("warning: Could not save the history: {error}");
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,143 @@
+//! The numeric types terms can be evaluated with.
+
+use anyhow::Result;
+
+use crate::{Constant, MyError, Rational, UnaryOperation};
+
+/// A number type terms can be evaluated with.
+///
+/// Every operation may fail, each backend decides which inputs are outside of its domain.
+pub trait Number: Clone {
+    /// Converts a literal of the term.
+    fn from_f64(x: f64) -> Result<Self>;
+
+    /// Converts a constant, failing for the imaginary unit by default.
+    fn from_constant(constant: Constant) -> Result<Self> {
+        match constant.value() {
+            Some(x) => Self::from_f64(x),
+            None => Err(MyError::NotReal {
+                expression: constant.to_string(),
+            })?,
+        }
+    }
+
+    fn from_rational(x: &Rational) -> Result<Self> {
+        Self::from_f64(x.to_f64())
+    }
+
+    fn plus(self, other: Self) -> Result<Self>;
+    fn minus(self, other: Self) -> Result<Self>;
+    fn multiply(self, other: Self) -> Result<Self>;
+    fn divide(self, other: Self) -> Result<Self>;
+    fn pow(self, exponent: Self) -> Result<Self>;
+    fn unary(self, operation: UnaryOperation) -> Result<Self>;
+}
+
+impl Number for f64 {
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(x)
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(self + other)
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(self - other)
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        Ok(self * other)
+    }
+
+    fn divide(self, other: Self) -> Result<Self> {
+        if other == 0. {
+            Err(MyError::DivisionByZero)?
+        }
+        Ok(self / other)
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        Ok(self.powf(exponent))
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        let x = self;
+        let out_of_domain = || MyError::OutOfDomain {
+            operation,
+            value: x.to_string(),
+        };
+
+        let result = match operation {
+            UnaryOperation::Negate => -x,
+            UnaryOperation::Abs => x.abs(),
+            UnaryOperation::Sqrt if x < 0. => Err(MyError::RootOfNegative { value: x })?,
+            UnaryOperation::Sqrt => x.sqrt(),
+            UnaryOperation::Exp => x.exp(),
+            UnaryOperation::Ln | UnaryOperation::Log(_) if x <= 0. => {
+                Err(MyError::LogarithmOfNonPositive { value: x })?
+            }
+            UnaryOperation::Log(base) if base <= 0. || base == 1. => {
+                Err(MyError::InvalidLogarithmBase { base })?
+            }
+            UnaryOperation::Ln => x.ln(),
+            UnaryOperation::Log(base) => x.log(base),
+            UnaryOperation::Sin => x.sin(),
+            UnaryOperation::Cos => x.cos(),
+            UnaryOperation::Tan => x.tan(),
+            UnaryOperation::Asin | UnaryOperation::Acos if !(-1. ..=1.).contains(&x) => {
+                Err(out_of_domain())?
+            }
+            UnaryOperation::Asin => x.asin(),
+            UnaryOperation::Acos => x.acos(),
+            UnaryOperation::Atan => x.atan(),
+            UnaryOperation::Sinh => x.sinh(),
+            UnaryOperation::Cosh => x.cosh(),
+            UnaryOperation::Tanh => x.tanh(),
+            UnaryOperation::Asinh => x.asinh(),
+            UnaryOperation::Acosh if x < 1. => Err(out_of_domain())?,
+            UnaryOperation::Acosh => x.acosh(),
+            UnaryOperation::Atanh if x.abs() >= 1. => Err(out_of_domain())?,
+            UnaryOperation::Atanh => x.atanh(),
+            UnaryOperation::Floor => x.floor(),
+            UnaryOperation::Ceil => x.ceil(),
+            UnaryOperation::Round => x.round(),
+            // `f64::signum` is 1 for zero.
+            UnaryOperation::Sign if x == 0. => 0.,
+            UnaryOperation::Sign => x.signum(),
+        };
+
+        Ok(result)
+    }
+}
+
+/// Single precision, with division and unary operations going through `f64` for its domain checks.
+impl Number for f32 {
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(x as f32)
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(self + other)
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(self - other)
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        Ok(self * other)
+    }
+
+    fn divide(self, other: Self) -> Result<Self> {
+        Ok(f64::from(self).divide(f64::from(other))? as f32)
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        Ok(self.powf(exponent))
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        Ok(f64::from(self).unary(operation)? as f32)
+    }
+}
@@ -0,0 +1,213 @@
+//! Multi-threaded evaluation over evenly spaced ranges and grids.
+
+use std::{
+    panic,
+    thread::{self, available_parallelism},
+};
+
+use anyhow::Result;
+
+use crate::{Function, FunctionTerm, MyError, Program};
+
+// Everything that is shared between the worker threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Function>();
+    assert_send_sync::<FunctionTerm>();
+    assert_send_sync::<Program>();
+};
+
+/// `count` evenly spaced points from `start` to `end`, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+impl Range {
+    pub fn new(start: f64, end: f64, count: usize) -> Range {
+        Range { start, end, count }
+    }
+
+    pub fn at(&self, index: usize) -> f64 {
+        match self.count {
+            0 | 1 => self.start,
+            count => self.start + (self.end - self.start) * index as f64 / (count - 1) as f64,
+        }
+    }
+}
+
+impl Function {
+    /// Evaluates a function of one argument at every point of `x`, see [`Program::eval_range_parallel`].
+    pub fn eval_range_parallel(&self, x: Range, threads: usize) -> Result<Vec<f64>> {
+        self.compile()?.eval_range_parallel(x, threads)
+    }
+
+    /// Evaluates a function of two arguments on a grid, see [`Program::eval_grid_parallel`].
+    pub fn eval_grid_parallel(&self, x: Range, y: Range, threads: usize) -> Result<Vec<f64>> {
+        self.compile()?.eval_grid_parallel(x, y, threads)
+    }
+}
+
+impl Program {
+    /// Evaluates at every point of `x`, in order, on `threads` threads.
+    ///
+    /// A thread count of 0 uses one thread per available core.
+    pub fn eval_range_parallel(&self, x: Range, threads: usize) -> Result<Vec<f64>> {
+        self.expect_arity(1)?;
+        self.eval_parallel(x.count, threads, |i| [x.at(i), 0.])
+    }
+
+    /// Evaluates at every `(x, y)` of the grid on `threads` threads.
+    ///
+    /// The result is row major, the value for `x.at(i)` and `y.at(j)` is at `j * x.count + i`.
+    /// A thread count of 0 uses one thread per available core.
+    pub fn eval_grid_parallel(&self, x: Range, y: Range, threads: usize) -> Result<Vec<f64>> {
+        self.expect_arity(2)?;
+        let Some(len) = x.count.checked_mul(y.count) else {
+            Err(MyError::GridTooLarge {
+                columns: x.count,
+                rows: y.count,
+            })?
+        };
+        self.eval_parallel(len, threads, |index| {
+            [x.at(index % x.count), y.at(index / x.count)]
+        })
+    }
+
+    pub(crate) fn expect_arity(&self, arity: usize) -> Result<()> {
+        if self.arity != arity {
+            Err(MyError::ArityMismatch {
+                expected: self.arity,
+                given: arity,
+            })?
+        }
+        Ok(())
+    }
+
+    /// Splits `len` points into one contiguous chunk per thread, so the output order is fixed.
+    fn eval_parallel(
+        &self,
+        len: usize,
+        threads: usize,
+        point: impl Fn(usize) -> [f64; 2] + Sync,
+    ) -> Result<Vec<f64>> {
+        let threads = match threads {
+            0 => available_parallelism().map_or(1, |x| x.get()),
+            threads => threads,
+        };
+        let chunk = len.div_ceil(threads).max(1);
+        let point = &point;
+
+        let mut output = vec![0.; len];
+        let failures: Vec<Option<(usize, anyhow::Error)>> = thread::scope(|scope| {
+            let workers: Vec<_> = output
+                .chunks_mut(chunk)
+                .enumerate()
+                .map(|(n, values)| {
+                    scope.spawn(move || {
+                        // The only allocation of the worker, reused for every point.
+                        let mut stack = Vec::with_capacity(self.stack_size);
+                        for (k, value) in values.iter_mut().enumerate() {
+                            let index = n * chunk + k;
+                            match self.eval_with(&point(index)[..self.arity], &mut stack) {
+                                Ok(x) => *value = x,
+                                Err(error) => return Some((index, error)),
+                            }
+                        }
+                        None
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+
+        // Chunks are in order, so this is the failure with the lowest index.
+        match failures.into_iter().flatten().next() {
+            Some((index, error)) => {
+                Err(error.context(format!("Failed to evaluate point {index}.")))
+            }
+            None => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, MyError};
+
+    use super::Range;
+
+    fn error(error: anyhow::Error) -> MyError {
+        error.downcast().unwrap()
+    }
+
+    #[test]
+    fn ranges() {
+        let f: Function = "x^2".parse().unwrap();
+        for threads in [0, 1, 3, 16] {
+            let values = f
+                .eval_range_parallel(Range::new(0., 2., 5), threads)
+                .unwrap();
+            assert_eq!(values, [0., 0.25, 1., 2.25, 4.]);
+        }
+        assert_eq!(
+            f.eval_range_parallel(Range::new(3., 7., 1), 2).unwrap(),
+            [9.]
+        );
+        assert!(f
+            .eval_range_parallel(Range::new(0., 1., 0), 4)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn grids_are_row_major() {
+        let f: Function = "10 * y + x".parse().unwrap();
+        let (x, y) = (Range::new(0., 2., 3), Range::new(0., 1., 2));
+        for threads in [1, 2, 4, 7] {
+            let values = f.eval_grid_parallel(x, y, threads).unwrap();
+            assert_eq!(values, [0., 1., 2., 10., 11., 12.]);
+        }
+        assert!(f
+            .eval_grid_parallel(x, Range::new(0., 1., 0), 2)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let f: Function = "x * y".parse().unwrap();
+        let x = Range::new(0., 1., 2);
+        assert!(matches!(
+            error(f.eval_range_parallel(x, 1).unwrap_err()),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        assert!(matches!(
+            error(
+                f.eval_grid_parallel(Range::new(0., 1., usize::MAX), Range::new(0., 1., 2), 1)
+                    .unwrap_err()
+            ),
+            MyError::GridTooLarge {
+                columns: usize::MAX,
+                rows: 2
+            }
+        ));
+
+        // The failure with the lowest index is reported, whichever thread finishes first.
+        let f: Function = "1 / (x - 2) + 1 / (x - 7)".parse().unwrap();
+        let error = f
+            .eval_range_parallel(Range::new(0., 9., 10), 4)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Failed to evaluate point 2.");
+        assert!(matches!(error.downcast().unwrap(), MyError::DivisionByZero));
+    }
+}
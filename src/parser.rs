@@ -0,0 +1,420 @@
+use std::{iter::Peekable, str::FromStr, vec::IntoIter};
+
+use anyhow::Result;
+
+use crate::{
+    Constant, Function, FunctionTerm, Identifier, MyError, Operation, UnaryOperation, Value,
+    NEGATION_PRECEDENCE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Variable(Identifier),
+    Constant(Constant),
+    Function(UnaryOperation),
+    Operator(Operation),
+    OpenParenthesis,
+    CloseParenthesis,
+    Comma,
+}
+
+#[derive(Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    position: usize,
+}
+
+impl Token {
+    fn unexpected(self) -> MyError {
+        MyError::UnexpectedToken {
+            token: self.text,
+            position: self.position,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < chars.len() {
+        let start = position;
+        let character = chars[position];
+        position += 1;
+
+        let kind = match character {
+            c if c.is_whitespace() => continue,
+            '+' => TokenKind::Operator(Operation::Plus),
+            '-' => TokenKind::Operator(Operation::Minus),
+            '*' => TokenKind::Operator(Operation::Multiply),
+            '/' => TokenKind::Operator(Operation::Divide),
+            '^' => TokenKind::Operator(Operation::Pow),
+            '(' => TokenKind::OpenParenthesis,
+            ')' => TokenKind::CloseParenthesis,
+            ',' => TokenKind::Comma,
+            c if c.is_ascii_digit() || c == '.' => {
+                while position < chars.len()
+                    && (chars[position].is_ascii_digit() || chars[position] == '.')
+                {
+                    position += 1;
+                }
+                let literal: String = chars[start..position].iter().collect();
+                let value = literal.parse().map_err(|_| MyError::InvalidNumber {
+                    literal: literal.clone(),
+                    position: start,
+                })?;
+                TokenKind::Number(value)
+            }
+            c if c.is_alphabetic() => {
+                while position < chars.len()
+                    && (chars[position].is_alphanumeric() || chars[position] == '_')
+                {
+                    position += 1;
+                }
+                let name: String = chars[start..position].iter().collect();
+                if let Some(operation) = UnaryOperation::from_name(&name) {
+                    TokenKind::Function(operation)
+                } else if let Some(constant) = Constant::from_name(&name) {
+                    TokenKind::Constant(constant)
+                } else {
+                    TokenKind::Variable(Identifier::new(&name))
+                }
+            }
+            character => Err(MyError::UnexpectedCharacter {
+                character,
+                position: start,
+            })?,
+        };
+
+        tokens.push(Token {
+            kind,
+            text: chars[start..position].iter().collect(),
+            position: start,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Deeper nesting, by parentheses or by chains of operators, would overflow the stack while
+/// parsing or evaluating the term.
+const MAX_NESTING: usize = 256;
+
+/// Precedence climbing parser over the token stream.
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<TokenKind> {
+        self.tokens.peek().map(|token| token.kind)
+    }
+
+    fn parse(mut self) -> Result<FunctionTerm> {
+        let term = self.expression(0)?;
+        match self.tokens.next() {
+            Some(token) => Err(token.unexpected())?,
+            None => Ok(term),
+        }
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<FunctionTerm> {
+        self.nest()?;
+        let term = self.binary(min_precedence);
+        self.depth -= 1;
+        term
+    }
+
+    /// Goes one level deeper into the tree, failing at the position of the next token if that
+    /// is too deep.
+    fn nest(&mut self) -> Result<()> {
+        if self.depth == MAX_NESTING {
+            let position = self.tokens.peek().map_or(0, |token| token.position);
+            Err(MyError::TooDeeplyNested { position })?
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<FunctionTerm> {
+        let depth = self.depth;
+        let term = self.chain(min_precedence);
+        self.depth = depth;
+        term
+    }
+
+    /// Parses operands joined by operators of at least `min_precedence`. Every operator nests
+    /// the term one level deeper, since `1 + 1 + 1` is `(1 + 1) + 1`.
+    fn chain(&mut self, min_precedence: u8) -> Result<FunctionTerm> {
+        let mut left = self.unary()?;
+
+        while let Some(TokenKind::Operator(operation)) = self.peek() {
+            let precedence = operation.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.nest()?;
+            self.tokens.next();
+
+            let next_precedence = if operation.is_right_associative() {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let right = self.expression(next_precedence)?;
+
+            left = FunctionTerm::Calculation {
+                left: left.into(),
+                right: right.into(),
+                operation,
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<FunctionTerm> {
+        if self.peek() != Some(TokenKind::Operator(Operation::Minus)) {
+            return self.primary();
+        }
+        self.tokens.next();
+
+        // Only `-2` is a negative literal, `-(2)` stays a negation.
+        let is_literal = matches!(self.peek(), Some(TokenKind::Number(_)));
+        Ok(match self.expression(NEGATION_PRECEDENCE)? {
+            FunctionTerm::Value(Value::Literal(x)) if is_literal => {
+                FunctionTerm::Value(Value::Literal(-x))
+            }
+            term => -term,
+        })
+    }
+
+    fn primary(&mut self) -> Result<FunctionTerm> {
+        let token = self.tokens.next().ok_or(MyError::UnexpectedEnd)?;
+
+        let term = match token.kind {
+            TokenKind::Number(x) => FunctionTerm::Value(Value::Literal(x)),
+            TokenKind::Variable(x) => FunctionTerm::Variable(x),
+            TokenKind::Constant(x) => FunctionTerm::Value(Value::Constant(x)),
+            TokenKind::OpenParenthesis => self.parenthesized(token.position)?,
+            TokenKind::Function(operation) => match self.tokens.next() {
+                Some(Token {
+                    kind: TokenKind::OpenParenthesis,
+                    position,
+                    ..
+                }) => {
+                    let operation = match operation {
+                        UnaryOperation::Log(base) => UnaryOperation::Log(self.log_base(base)),
+                        operation => operation,
+                    };
+                    self.parenthesized(position)?.unary(operation)
+                }
+                Some(token) => Err(token.unexpected())?,
+                None => Err(MyError::UnexpectedEnd)?,
+            },
+            _ => Err(token.unexpected())?,
+        };
+
+        Ok(term)
+    }
+
+    /// Consumes the explicit base of `log(b, x)`, falling back to `default` for `log(x)`.
+    fn log_base(&mut self, default: f64) -> f64 {
+        let mut lookahead = self.tokens.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (
+                Some(Token {
+                    kind: TokenKind::Number(base),
+                    ..
+                }),
+                Some(Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }),
+            ) => {
+                self.tokens = lookahead;
+                base
+            }
+            _ => default,
+        }
+    }
+
+    /// Parses the rest of a parenthesized expression opened at `position`.
+    fn parenthesized(&mut self, position: usize) -> Result<FunctionTerm> {
+        let term = self.expression(0)?;
+        match self.tokens.next() {
+            Some(Token {
+                kind: TokenKind::CloseParenthesis,
+                ..
+            }) => Ok(term),
+            Some(token) => Err(token.unexpected())?,
+            None => Err(MyError::UnclosedParenthesis { position })?,
+        }
+    }
+}
+
+impl FromStr for FunctionTerm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+            depth: 0,
+        }
+        .parse()
+    }
+}
+
+impl FromStr for Function {
+    type Err = anyhow::Error;
+
+    /// Parses an infix expression, taking its variables as the arguments.
+    fn from_str(s: &str) -> Result<Self> {
+        let term: FunctionTerm = s.parse()?;
+        Ok(Function {
+            arguments: term.variables(),
+            term,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Function, FunctionTerm, Identifier, MyError, Operation, UnaryOperation, Value};
+
+    fn eval(input: &str, args: &[(&str, f64)]) -> f64 {
+        let term: FunctionTerm = input.parse().unwrap();
+        let args: HashMap<Identifier, f64> =
+            args.iter().map(|&(x, v)| (Identifier::new(x), v)).collect();
+        term.solve(&args).unwrap()
+    }
+
+    fn error(input: &str) -> MyError {
+        let error = input.parse::<FunctionTerm>().unwrap_err();
+        error.downcast().unwrap()
+    }
+
+    fn literal(x: f64) -> Box<FunctionTerm> {
+        FunctionTerm::Value(Value::Literal(x)).into()
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3", &[]), 7.);
+        assert_eq!(eval("(1 + 2) * 3", &[]), 9.);
+        assert_eq!(eval("2^3^2", &[]), 512.);
+        assert_eq!(eval("(2^3)^2", &[]), 64.);
+        assert_eq!(eval("-x^2", &[("x", 3.)]), -9.);
+        assert_eq!(eval("(-x)^2", &[("x", 3.)]), 9.);
+        assert_eq!(eval("a - b - c", &[("a", 10.), ("b", 3.), ("c", 2.)]), 5.);
+        assert_eq!(eval("a / b / c", &[("a", 12.), ("b", 3.), ("c", 2.)]), 2.);
+        assert_eq!(eval("2 * -3", &[]), -6.);
+        assert_eq!(eval("--2", &[]), 2.);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        let term: FunctionTerm = "2^3^2".parse().unwrap();
+        let expected = FunctionTerm::Calculation {
+            left: literal(2.),
+            right: FunctionTerm::Calculation {
+                left: literal(3.),
+                right: literal(2.),
+                operation: Operation::Pow,
+            }
+            .into(),
+            operation: Operation::Pow,
+        };
+        assert_eq!(term, expected);
+    }
+
+    #[test]
+    fn negative_literals_and_negations() {
+        let literal: FunctionTerm = "-2".parse().unwrap();
+        assert_eq!(literal, FunctionTerm::Value(Value::Literal(-2.)));
+        let negation: FunctionTerm = "-(2)".parse().unwrap();
+        assert_eq!(
+            negation,
+            FunctionTerm::Value(Value::Literal(2.)).unary(UnaryOperation::Negate)
+        );
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        assert_eq!(eval("log(2, 8)", &[]), 3.);
+        assert!((eval("log(1000)", &[]) - 3.).abs() < 1e-15);
+        assert_eq!(eval("sqrt(16) + abs(-2)", &[]), 6.);
+        assert_eq!(eval("cos(pi)", &[]), -1.);
+        assert_eq!(eval("ln(e)", &[]), 1.);
+    }
+
+    #[test]
+    fn logarithm_bases_must_be_literals() {
+        assert!(matches!(
+            error("log(y, x)"),
+            MyError::UnexpectedToken { position: 5, .. }
+        ));
+    }
+
+    #[test]
+    fn arguments_are_inferred() {
+        let f: Function = "3*x^2 + 2*y - 1 + theta".parse().unwrap();
+        let names: Vec<&str> = f.arguments.iter().map(|x| x.name()).collect();
+        assert_eq!(names, ["theta", "x", "y"]);
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        assert!(matches!(
+            error("1 + * 2"),
+            MyError::UnexpectedToken { position: 4, .. }
+        ));
+        assert!(matches!(
+            error("2 * (1 + 2"),
+            MyError::UnclosedParenthesis { position: 4 }
+        ));
+        assert!(matches!(
+            error("2 $ 3"),
+            MyError::UnexpectedCharacter {
+                character: '$',
+                position: 2
+            }
+        ));
+        assert!(matches!(
+            error("1 + 1..2"),
+            MyError::InvalidNumber { position: 4, .. }
+        ));
+        assert!(matches!(error("1 +"), MyError::UnexpectedEnd));
+        assert!(matches!(
+            error("(1 + 2))"),
+            MyError::UnexpectedToken { position: 7, .. }
+        ));
+        assert!(matches!(
+            error("sin x"),
+            MyError::UnexpectedToken { position: 4, .. }
+        ));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let deep = format!("{}1{}", "(".repeat(300), ")".repeat(300));
+        assert!(matches!(error(&deep), MyError::TooDeeplyNested { .. }));
+        let long = format!("1{}", "+1".repeat(300));
+        assert!(matches!(error(&long), MyError::TooDeeplyNested { .. }));
+        let negations = format!("{}1", "-".repeat(300));
+        assert!(matches!(error(&negations), MyError::TooDeeplyNested { .. }));
+
+        let fine = format!(
+            "{}1{}",
+            "(".repeat(100),
+            "+1".repeat(100) + &")".repeat(100)
+        );
+        assert_eq!(eval(&fine, &[]), 101.);
+    }
+}
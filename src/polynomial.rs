@@ -0,0 +1,479 @@
+//! Polynomials in one variable with dense coefficients.
+
+use std::{
+    collections::HashMap,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use anyhow::Result;
+
+use crate::{Complex, Function, FunctionTerm, Identifier, MyError, Operation, UnaryOperation};
+
+/// The highest degree [`Polynomial::from_term`] builds, so that `x^1e9` fails instead of
+/// allocating gigabytes.
+const MAX_DEGREE: usize = 1 << 16;
+
+/// Remainders below this, relative to the largest coefficient, count as zero in
+/// [`Polynomial::gcd`].
+pub(crate) const GCD_TOLERANCE: f64 = 1e-10;
+
+/// A polynomial as its coefficients, lowest degree first and without trailing zeros.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polynomial {
+    pub coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    pub fn new(mut coefficients: Vec<f64>) -> Polynomial {
+        while coefficients.last() == Some(&0.) {
+            coefficients.pop();
+        }
+        Polynomial { coefficients }
+    }
+
+    pub fn constant(c: f64) -> Polynomial {
+        Polynomial::new(vec![c])
+    }
+
+    /// The polynomial `x`.
+    pub fn identity() -> Polynomial {
+        Polynomial::new(vec![0., 1.])
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// The degree, `None` for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    /// The coefficient of the highest power, zero for the zero polynomial.
+    pub fn leading(&self) -> f64 {
+        self.coefficients.last().copied().unwrap_or(0.)
+    }
+
+    /// Evaluates by Horner's scheme.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients.iter().rev().fold(0., |y, c| y * x + c)
+    }
+
+    /// Evaluates at a complex point by Horner's scheme.
+    pub fn eval_complex(&self, z: Complex) -> Complex {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Complex::default(), |y, &c| y.mul(z).add(c.into()))
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        Polynomial::new(
+            self.coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| i as f64 * c)
+                .collect(),
+        )
+    }
+
+    fn scale(&self, factor: f64) -> Polynomial {
+        Polynomial::new(self.coefficients.iter().map(|c| c * factor).collect())
+    }
+
+    /// The same polynomial with a leading coefficient of one, the zero polynomial stays zero.
+    pub fn monic(&self) -> Polynomial {
+        match self.is_zero() {
+            true => self.clone(),
+            false => self.scale(1. / self.leading()),
+        }
+    }
+
+    /// The quotient and remainder of long division, the remainder having a lower degree than
+    /// `divisor`.
+    pub fn div_rem(&self, divisor: &Polynomial) -> Result<(Polynomial, Polynomial)> {
+        let Some(degree) = divisor.degree() else {
+            Err(MyError::DivisionByZero)?
+        };
+        let Some(shift) = self.degree().and_then(|d| d.checked_sub(degree)) else {
+            return Ok((Polynomial::default(), self.clone()));
+        };
+
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![0.; shift + 1];
+        for i in (0..=shift).rev() {
+            let factor = remainder[i + degree] / divisor.leading();
+            quotient[i] = factor;
+            for (j, c) in divisor.coefficients.iter().enumerate() {
+                remainder[i + j] -= factor * c;
+            }
+            // Exactly zero, and not whatever the rounding left over.
+            remainder[i + degree] = 0.;
+        }
+
+        Ok((Polynomial::new(quotient), Polynomial::new(remainder)))
+    }
+
+    /// The monic greatest common divisor by the Euclidean algorithm.
+    ///
+    /// Remainders that are zero up to rounding are treated as zero, so the divisor found is
+    /// only as exact as the coefficients.
+    pub fn gcd(&self, other: &Polynomial) -> Polynomial {
+        let (mut a, mut b) = (self.monic(), other.monic());
+        while !b.is_zero() {
+            let (_, remainder) = a.div_rem(&b).expect("the divisor is not zero");
+            let scale = a.max_coefficient().max(b.max_coefficient());
+            (a, b) = (b, remainder.chop(GCD_TOLERANCE * scale).monic());
+        }
+        a
+    }
+
+    /// The same polynomial with every coefficient of at most `tolerance` set to zero.
+    pub(crate) fn chop(&self, tolerance: f64) -> Polynomial {
+        Polynomial::new(
+            self.coefficients
+                .iter()
+                .map(|&c| match c.abs() <= tolerance {
+                    true => 0.,
+                    false => c,
+                })
+                .collect(),
+        )
+    }
+
+    /// The largest absolute value of a coefficient.
+    pub(crate) fn max_coefficient(&self) -> f64 {
+        self.coefficients
+            .iter()
+            .fold(0., |max: f64, c| max.max(c.abs()))
+    }
+
+    /// The polynomial `self(inner(x))`.
+    pub fn compose(&self, inner: &Polynomial) -> Polynomial {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Polynomial::default(), |y, &c| {
+                &(&y * inner) + &Polynomial::constant(c)
+            })
+    }
+
+    pub fn pow(&self, exponent: usize) -> Polynomial {
+        let (mut base, mut exponent) = (self.clone(), exponent);
+        let mut result = Polynomial::constant(1.);
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            exponent /= 2;
+        }
+        result
+    }
+
+    /// The polynomial in `variable` that `term` is, treating every other part as a constant
+    /// that must evaluate to a number.
+    pub fn from_term(term: &FunctionTerm, variable: Identifier) -> Result<Polynomial> {
+        let not_polynomial = || MyError::NotPolynomial {
+            term: term.to_string(),
+            variable,
+        };
+        if !term.contains(variable) {
+            let c = term
+                .solve::<f64>(&HashMap::new())
+                .map_err(|_| not_polynomial())?;
+            return Ok(Polynomial::constant(c));
+        }
+
+        let polynomial = match term {
+            FunctionTerm::Variable(_) => Polynomial::identity(),
+            FunctionTerm::Unary {
+                term,
+                operation: UnaryOperation::Negate,
+            } => -&Polynomial::from_term(term, variable)?,
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let left = Polynomial::from_term(left, variable)?;
+                match operation {
+                    Operation::Plus => &left + &Polynomial::from_term(right, variable)?,
+                    Operation::Minus => &left - &Polynomial::from_term(right, variable)?,
+                    Operation::Multiply => &left * &Polynomial::from_term(right, variable)?,
+                    Operation::Divide => {
+                        let right = Polynomial::from_term(right, variable)?;
+                        match right.degree() {
+                            Some(0) => left.scale(1. / right.leading()),
+                            Some(_) => Err(not_polynomial())?,
+                            None => Err(MyError::DivisionByZero)?,
+                        }
+                    }
+                    Operation::Pow => {
+                        let exponent = match right.contains(variable) {
+                            true => None,
+                            false => right.solve::<f64>(&HashMap::new()).ok(),
+                        };
+                        let exponent = match exponent {
+                            Some(n) if n >= 0. && n.fract() == 0. => n,
+                            _ => Err(not_polynomial())?,
+                        };
+                        let degree = left.degree().unwrap_or(0) as f64 * exponent;
+                        if degree > MAX_DEGREE as f64 {
+                            Err(MyError::ExponentTooLarge {
+                                exponent: exponent.to_string(),
+                            })?
+                        }
+                        left.pow(exponent as usize)
+                    }
+                }
+            }
+            FunctionTerm::Unary { .. } | FunctionTerm::Value(_) => Err(not_polynomial())?,
+        };
+
+        Ok(polynomial)
+    }
+
+    /// The sum of the non-zero terms in `variable`, highest power first.
+    pub fn to_term(&self, variable: Identifier) -> FunctionTerm {
+        let mut term: Option<FunctionTerm> = None;
+        for (i, &c) in self.coefficients.iter().enumerate().rev() {
+            if c == 0. {
+                continue;
+            }
+
+            let power = match i {
+                0 => None,
+                1 => Some(FunctionTerm::Variable(variable)),
+                i => Some(FunctionTerm::Variable(variable).pow((i as f64).into())),
+            };
+            let magnitude = match (power, term.is_some()) {
+                (None, true) => c.abs().into(),
+                (None, false) => c.into(),
+                (Some(power), true) if c.abs() == 1. => power,
+                (Some(power), false) if c == 1. => power,
+                (Some(power), false) if c == -1. => -power,
+                (Some(power), true) => FunctionTerm::from(c.abs()) * power,
+                (Some(power), false) => FunctionTerm::from(c) * power,
+            };
+
+            term = Some(match term {
+                None => magnitude,
+                Some(term) if c < 0. => term - magnitude,
+                Some(term) => term + magnitude,
+            });
+        }
+        term.unwrap_or(0.0.into())
+    }
+}
+
+impl Function {
+    /// The term of a function of one argument as a polynomial in that argument.
+    pub fn to_polynomial(&self) -> Result<Polynomial> {
+        match self.arguments[..] {
+            [variable] => Polynomial::from_term(&self.term, variable),
+            _ => Err(MyError::ArityMismatch {
+                expected: self.arguments.len(),
+                given: 1,
+            })?,
+        }
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, rhs: &Polynomial) -> Polynomial {
+        let length = self.coefficients.len().max(rhs.coefficients.len());
+        let coefficient = |p: &Polynomial, i: usize| p.coefficients.get(i).copied().unwrap_or(0.);
+        Polynomial::new(
+            (0..length)
+                .map(|i| coefficient(self, i) + coefficient(rhs, i))
+                .collect(),
+        )
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, rhs: &Polynomial) -> Polynomial {
+        self + &-rhs
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, rhs: &Polynomial) -> Polynomial {
+        if self.is_zero() || rhs.is_zero() {
+            return Polynomial::default();
+        }
+
+        let mut coefficients = vec![0.; self.coefficients.len() + rhs.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in rhs.coefficients.iter().enumerate() {
+                coefficients[i + j] += a * b;
+            }
+        }
+        Polynomial::new(coefficients)
+    }
+}
+
+impl Neg for &Polynomial {
+    type Output = Polynomial;
+
+    fn neg(self) -> Polynomial {
+        self.scale(-1.)
+    }
+}
+
+macro_rules! impl_polynomial_operation {
+    ($trait:ident, $method:ident) => {
+        impl $trait for Polynomial {
+            type Output = Polynomial;
+
+            fn $method(self, rhs: Polynomial) -> Polynomial {
+                (&self).$method(&rhs)
+            }
+        }
+    };
+}
+
+impl_polynomial_operation!(Add, add);
+impl_polynomial_operation!(Sub, sub);
+impl_polynomial_operation!(Mul, mul);
+
+impl Neg for Polynomial {
+    type Output = Polynomial;
+
+    fn neg(self) -> Polynomial {
+        -&self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, FunctionTerm, Identifier, MyError};
+
+    use super::Polynomial;
+
+    fn polynomial(coefficients: &[f64]) -> Polynomial {
+        Polynomial::new(coefficients.to_vec())
+    }
+
+    fn from_term(input: &str) -> anyhow::Result<Polynomial> {
+        let term: FunctionTerm = input.parse().unwrap();
+        Polynomial::from_term(&term, Identifier::new("x"))
+    }
+
+    #[test]
+    fn arithmetic() {
+        let p = polynomial(&[1., 2., 3.]);
+        let q = polynomial(&[-1., 0., -3.]);
+        assert_eq!(&p + &q, polynomial(&[0., 2.]));
+        assert_eq!(&p - &p, Polynomial::default());
+        assert_eq!(&p * &q, polynomial(&[-1., -2., -6., -6., -9.]));
+        assert_eq!(-&p, polynomial(&[-1., -2., -3.]));
+        assert_eq!(p.pow(0), Polynomial::constant(1.));
+        assert_eq!(polynomial(&[1., 1.]).pow(3), polynomial(&[1., 3., 3., 1.]));
+        assert_eq!(p.derivative(), polynomial(&[2., 6.]));
+        assert_eq!(p.compose(&polynomial(&[1., 1.])), polynomial(&[6., 8., 3.]));
+        assert_eq!(p.eval(2.), 17.);
+        assert_eq!(p.monic(), polynomial(&[1. / 3., 2. / 3., 1.]));
+
+        assert_eq!(polynomial(&[1., 0., 0.]).degree(), Some(0));
+        assert_eq!(Polynomial::default().degree(), None);
+        assert_eq!(Polynomial::default().leading(), 0.);
+        assert_eq!(Polynomial::default().monic(), Polynomial::default());
+    }
+
+    #[test]
+    fn division() {
+        // x^3 - 2x^2 - 4 = (x - 3)(x^2 + x + 3) + 5
+        let p = polynomial(&[-4., 0., -2., 1.]);
+        let divisor = polynomial(&[-3., 1.]);
+        let (quotient, remainder) = p.div_rem(&divisor).unwrap();
+        assert_eq!(quotient, polynomial(&[3., 1., 1.]));
+        assert_eq!(remainder, Polynomial::constant(5.));
+        assert_eq!(&(&quotient * &divisor) + &remainder, p);
+
+        let (quotient, remainder) = divisor.div_rem(&p).unwrap();
+        assert_eq!((quotient, remainder), (Polynomial::default(), divisor));
+
+        let error: MyError = p
+            .div_rem(&Polynomial::default())
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(error, MyError::DivisionByZero));
+    }
+
+    #[test]
+    fn greatest_common_divisors() {
+        // (x - 1)(x + 2) and 3(x - 1)(x - 5)
+        let p = polynomial(&[-2., 1., 1.]);
+        let q = polynomial(&[15., -18., 3.]);
+        assert_eq!(p.gcd(&q), polynomial(&[-1., 1.]));
+        assert_eq!(p.gcd(&Polynomial::default()), p);
+        assert_eq!(p.gcd(&polynomial(&[1., 1.])), Polynomial::constant(1.));
+
+        // Rounding in the coefficients still leaves the common factor.
+        let r = &polynomial(&[0.1, 1.]) * &polynomial(&[0.3, 0.7, 1.]);
+        let s = &polynomial(&[0.1, 1.]) * &polynomial(&[-0.2, 1.]);
+        let gcd = r.gcd(&s);
+        assert_eq!(gcd.degree(), Some(1));
+        assert!((gcd.coefficients[0] - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn terms() {
+        for (input, expected) in [
+            ("3", &[3.][..]),
+            ("x", &[0., 1.]),
+            ("(x + 1)^2 - x", &[1., 1., 1.]),
+            ("x * (2 - x) / 4", &[0., 0.5, -0.25]),
+            ("-(x^3) + sqrt(4) * x", &[0., 2., 0., -1.]),
+            ("x^2 - x^2", &[]),
+        ] {
+            assert_eq!(from_term(input).unwrap(), polynomial(expected), "{input}");
+        }
+
+        for input in ["1 / x", "x^0.5", "x^x", "sin(x)", "x^-1", "2^x", "x * y"] {
+            let error: MyError = from_term(input).unwrap_err().downcast().unwrap();
+            assert!(matches!(error, MyError::NotPolynomial { .. }), "{input}");
+        }
+        let error: MyError = from_term("x^100000").unwrap_err().downcast().unwrap();
+        assert!(matches!(error, MyError::ExponentTooLarge { .. }));
+        let error: MyError = from_term("x / 0").unwrap_err().downcast().unwrap();
+        assert!(matches!(error, MyError::DivisionByZero));
+    }
+
+    #[test]
+    fn terms_round_trip() {
+        let x = Identifier::new("x");
+        for (coefficients, text) in [
+            (&[1., -3., 0., 2.][..], "2 * x^3 - 3 * x + 1"),
+            (&[-1., 1.], "x - 1"),
+            (&[0., 0., -1.], "-x^2"),
+            (&[2.5], "2.5"),
+            (&[], "0"),
+        ] {
+            let p = polynomial(coefficients);
+            let term = p.to_term(x);
+            assert_eq!(term.to_string(), text);
+            assert_eq!(Polynomial::from_term(&term, x).unwrap(), p);
+        }
+
+        let f: Function = "x * y".parse().unwrap();
+        let error: MyError = f.to_polynomial().unwrap_err().downcast().unwrap();
+        assert!(matches!(
+            error,
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+    }
+}
@@ -0,0 +1,510 @@
+//! All complex roots of a polynomial, by closed forms up to degree four and the Aberth–Ehrlich
+//! iteration above.
+
+use std::f64::consts::TAU;
+
+use anyhow::Result;
+
+use crate::{polynomial::GCD_TOLERANCE, Complex, MyError, Polynomial};
+
+/// How many Aberth steps are allowed before giving up.
+const MAX_ITERATIONS: usize = 500;
+
+/// Newton steps that polish every root against rounding in the closed forms.
+const POLISH_STEPS: usize = 3;
+
+/// Real or imaginary parts this small, relative to the root, are taken to be rounding.
+const NEGLIGIBLE: f64 = 1e-10;
+
+/// A root of a polynomial and how often it is repeated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Root {
+    pub value: Complex,
+    pub multiplicity: usize,
+}
+
+impl Polynomial {
+    /// Every root with its multiplicity, so the multiplicities add up to the degree.
+    ///
+    /// Repeated roots are found by splitting off square-free factors with [`Polynomial::gcd`],
+    /// whose roots are all simple and can be computed accurately.
+    pub fn roots(&self) -> Result<Vec<Root>> {
+        let Some(degree) = self.degree() else {
+            Err(MyError::ZeroPolynomial)?
+        };
+
+        // Roots at zero are exact and need no iteration.
+        let zeros = self.coefficients.iter().take_while(|&&c| c == 0.).count();
+        let reduced = Polynomial::new(self.coefficients[zeros..].to_vec());
+        let mut roots = Vec::new();
+        if zeros > 0 {
+            roots.push(Root {
+                value: Complex::default(),
+                multiplicity: zeros,
+            });
+        }
+
+        // Substituting x = s y moves the roots near the unit circle, where the tolerances of
+        // `gcd` fit. A power of two keeps the coefficients exact.
+        let n = reduced.degree().unwrap_or(0) as f64;
+        let size = (reduced.coefficients[0] / reduced.leading())
+            .abs()
+            .powf(1. / n);
+        let s = match size.is_normal() {
+            true => size.log2().round().exp2(),
+            false => 1.,
+        };
+        let scaled = reduced.compose(&Polynomial::new(vec![0., s]));
+
+        let factors = match scaled.square_free_factors() {
+            Some(factors) => factors,
+            // The factors did not come out consistently, so every root is reported on its own.
+            None => vec![(scaled, 1)],
+        };
+        for (factor, multiplicity) in factors {
+            for value in factor.simple_roots()? {
+                roots.push(Root {
+                    value: value.scale(s),
+                    multiplicity,
+                });
+            }
+        }
+
+        debug_assert_eq!(roots.iter().map(|x| x.multiplicity).sum::<usize>(), degree);
+        roots.sort_by(|x, y| {
+            (x.value.re.total_cmp(&y.value.re)).then(x.value.im.total_cmp(&y.value.im))
+        });
+        Ok(roots)
+    }
+
+    /// Yun's algorithm: factors that are each the product of all roots with one multiplicity,
+    /// or `None` if rounding broke the factorisation.
+    fn square_free_factors(&self) -> Option<Vec<(Polynomial, usize)>> {
+        let scale = self.max_coefficient();
+        let quotient = |p: &Polynomial, q: &Polynomial| p.div_rem(q).ok().map(|(q, _)| q);
+
+        let derivative = self.derivative();
+        let divisor = self.gcd(&derivative);
+        let mut b = quotient(self, &divisor)?;
+        let mut c = quotient(&derivative, &divisor)?;
+        let mut d = (&c - &b.derivative()).chop(GCD_TOLERANCE * scale);
+
+        let mut factors = Vec::new();
+        let mut multiplicity = 1;
+        while b.degree()? > 0 {
+            if multiplicity > self.degree()? {
+                return None;
+            }
+            let factor = b.gcd(&d);
+            b = quotient(&b, &factor)?;
+            c = quotient(&d, &factor)?;
+            d = (&c - &b.derivative()).chop(GCD_TOLERANCE * scale);
+            if factor.degree()? > 0 {
+                factors.push((factor, multiplicity));
+            }
+            multiplicity += 1;
+        }
+
+        let degree: usize = factors
+            .iter()
+            .map(|(factor, multiplicity)| factor.degree().unwrap_or(0) * multiplicity)
+            .sum();
+        (Some(degree) == self.degree()).then_some(factors)
+    }
+
+    /// The roots of a polynomial without repeated roots.
+    fn simple_roots(&self) -> Result<Vec<Complex>> {
+        let monic = self.monic();
+        let c = |i: usize| Complex::from(monic.coefficients[i]);
+        let roots = match monic.degree() {
+            None | Some(0) => Vec::new(),
+            Some(1) => vec![c(0).scale(-1.)],
+            Some(2) => quadratic(c(1), c(0)).to_vec(),
+            Some(3) => cubic(c(2), c(1), c(0)).to_vec(),
+            Some(4) => quartic(c(3), c(2), c(1), c(0)).to_vec(),
+            Some(_) => monic.aberth()?,
+        };
+
+        let derivative = monic.derivative();
+        let roots = roots
+            .into_iter()
+            .map(|z| monic.polish(&derivative, z))
+            .collect();
+        Ok(monic.conjugate_pairs(&derivative, roots))
+    }
+
+    /// The roots with the symmetry of a real polynomial: every root that is not real has its
+    /// conjugate as a partner, exactly.
+    ///
+    /// Imaginary parts within the error bound `|p(z)| / |p'(z)|` are rounding, and so is the
+    /// imaginary part of a root that has no conjugate to pair up with.
+    fn conjugate_pairs(&self, derivative: &Polynomial, mut roots: Vec<Complex>) -> Vec<Complex> {
+        for z in &mut roots {
+            let bound = self.eval_complex(*z).norm() / derivative.eval_complex(*z).norm();
+            if z.im.abs() <= bound {
+                z.im = 0.;
+            }
+        }
+
+        // The closest pairs of an upper and a lower root first.
+        let mut pairs: Vec<(usize, usize)> = (0..roots.len())
+            .filter(|&i| roots[i].im > 0.)
+            .flat_map(|i| (0..roots.len()).map(move |j| (i, j)))
+            .filter(|&(_, j)| roots[j].im < 0.)
+            .collect();
+        let distance = |(i, j): (usize, usize)| roots[i].sub(roots[j].conj()).norm();
+        pairs.sort_by(|&x, &y| distance(x).total_cmp(&distance(y)));
+
+        let mut paired = vec![false; roots.len()];
+        for (i, j) in pairs {
+            if paired[i] || paired[j] {
+                continue;
+            }
+            let z = roots[i].add(roots[j].conj()).scale(0.5);
+            (roots[i], roots[j]) = (z, z.conj());
+            (paired[i], paired[j]) = (true, true);
+        }
+        for (z, paired) in roots.iter_mut().zip(paired) {
+            if !paired {
+                z.im = 0.;
+            }
+        }
+        roots
+    }
+
+    /// A few Newton steps, as long as they make the value smaller.
+    fn polish(&self, derivative: &Polynomial, mut z: Complex) -> Complex {
+        let mut value = self.eval_complex(z).norm();
+        for _ in 0..POLISH_STEPS {
+            let next = z.sub(self.eval_complex(z).div(derivative.eval_complex(z)));
+            let next_value = self.eval_complex(next).norm();
+            if next_value.is_nan() || next_value >= value {
+                break;
+            }
+            (z, value) = (next, next_value);
+        }
+
+        let tiny = NEGLIGIBLE * z.norm();
+        if z.im.abs() <= tiny {
+            z.im = 0.;
+        }
+        if z.re.abs() <= tiny {
+            z.re = 0.;
+        }
+        z
+    }
+
+    /// The value at `|z|` with every coefficient made positive, which bounds the rounding error
+    /// of evaluating at `z`.
+    fn magnitude(&self, norm: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0., |y, c| y * norm + c.abs())
+    }
+
+    /// The Aberth–Ehrlich iteration, which moves all roots at once, each repelled by the others.
+    fn aberth(&self) -> Result<Vec<Complex>> {
+        let degree = self.degree().unwrap_or(0);
+        let derivative = self.derivative();
+        // Every root lies within this radius, by Cauchy's bound.
+        let radius = 1.
+            + self
+                .coefficients
+                .iter()
+                .fold(0., |max: f64, c| max.max(c.abs()));
+        let mut roots: Vec<Complex> = (0..degree)
+            .map(|k| {
+                // Off the real axis, so that conjugate pairs can separate.
+                let (sin, cos) = (TAU * k as f64 / degree as f64 + 0.4).sin_cos();
+                Complex::new(cos, sin).scale(radius / 2.)
+            })
+            .collect();
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut converged = true;
+            for i in 0..degree {
+                let z = roots[i];
+                let value = self.eval_complex(z);
+                // Nothing more can be learned once the value is all rounding error.
+                if value.norm() <= 8. * f64::EPSILON * self.magnitude(z.norm()) {
+                    continue;
+                }
+                let newton = value.div(derivative.eval_complex(z));
+                let repulsion = (0..degree)
+                    .filter(|&j| j != i)
+                    .fold(Complex::default(), |sum, j| {
+                        sum.add(Complex::new(1., 0.).div(z.sub(roots[j])))
+                    });
+                let step = newton.div(Complex::new(1., 0.).sub(newton.mul(repulsion)));
+                converged &= step.norm() <= 4. * f64::EPSILON * z.norm();
+                roots[i] = z.sub(step);
+            }
+            if converged {
+                return Ok(roots);
+            }
+        }
+
+        let estimate = roots.first().map(|z| z.re).unwrap_or(f64::NAN);
+        Err(MyError::NoConvergence {
+            iterations: MAX_ITERATIONS,
+            estimate,
+        })?
+    }
+}
+
+/// The roots of `z^2 + b z + c`, computed without cancellation.
+fn quadratic(b: Complex, c: Complex) -> [Complex; 2] {
+    let root = b.mul(b).sub(c.scale(4.)).sqrt();
+    // Add the root with the sign of `b`, so no digits cancel.
+    let root = match b.re * root.re + b.im * root.im >= 0. {
+        true => root,
+        false => root.scale(-1.),
+    };
+    let q = b.add(root).scale(-0.5);
+    match q == Complex::default() {
+        true => [q, q],
+        false => [q, c.div(q)],
+    }
+}
+
+/// The roots of `z^3 + a z^2 + b z + c` by Cardano's formula.
+fn cubic(a: Complex, b: Complex, c: Complex) -> [Complex; 3] {
+    // With z = t - a / 3 the equation becomes t^3 + p t + q = 0.
+    let shift = a.scale(-1. / 3.);
+    let p = b.sub(a.mul(a).scale(1. / 3.));
+    let q = a
+        .mul(a)
+        .mul(a)
+        .scale(2. / 27.)
+        .sub(a.mul(b).scale(1. / 3.))
+        .add(c);
+
+    let root = q
+        .mul(q)
+        .scale(0.25)
+        .add(p.mul(p).mul(p).scale(1. / 27.))
+        .sqrt();
+    let (plus, minus) = (q.scale(-0.5).add(root), q.scale(-0.5).sub(root));
+    let u = match plus.norm() >= minus.norm() {
+        true => plus,
+        false => minus,
+    }
+    .cbrt();
+    if u == Complex::default() {
+        return [shift; 3];
+    }
+
+    let third = Complex::new(-0.5, 3f64.sqrt() / 2.);
+    let mut rotation = Complex::new(1., 0.);
+    [(); 3].map(|_| {
+        let u = u.mul(rotation);
+        rotation = rotation.mul(third);
+        u.sub(p.div(u.scale(3.))).add(shift)
+    })
+}
+
+/// The roots of `z^4 + a z^3 + b z^2 + c z + d` by Ferrari's method.
+fn quartic(a: Complex, b: Complex, c: Complex, d: Complex) -> [Complex; 4] {
+    // With z = y - a / 4 the equation becomes y^4 + p y^2 + q y + r = 0.
+    let shift = a.scale(-0.25);
+    let a2 = a.mul(a);
+    let p = b.sub(a2.scale(3. / 8.));
+    let q = c.sub(a.mul(b).scale(0.5)).add(a2.mul(a).scale(1. / 8.));
+    let r = d
+        .sub(a.mul(c).scale(0.25))
+        .add(a2.mul(b).scale(1. / 16.))
+        .sub(a2.mul(a2).scale(3. / 256.));
+
+    let [y0, y1, y2, y3] = match q == Complex::default() {
+        // A quadratic in y^2.
+        true => {
+            let [u, v] = quadratic(p, r);
+            [u.sqrt(), u.sqrt().scale(-1.), v.sqrt(), v.sqrt().scale(-1.)]
+        }
+        // y^4 + p y^2 + q y + r = (y^2 + p/2 + m)^2 - (s y - q / (2 s))^2 with s^2 = 2 m, where m
+        // is a root of the resolvent cubic 8 m^3 + 8 p m^2 + (2 p^2 - 8 r) m - q^2.
+        false => {
+            let m = cubic(p, p.mul(p).scale(0.25).sub(r), q.mul(q).scale(-1. / 8.))
+                .into_iter()
+                .max_by(|x, y| x.norm().total_cmp(&y.norm()))
+                .unwrap_or_default();
+            let s = m.scale(2.).sqrt();
+            let base = p.scale(0.5).add(m);
+            let offset = q.div(s.scale(2.));
+            let [y0, y1] = quadratic(s.scale(-1.), base.add(offset));
+            let [y2, y3] = quadratic(s, base.sub(offset));
+            [y0, y1, y2, y3]
+        }
+    };
+    [y0, y1, y2, y3].map(|y| y.add(shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Complex, MyError, Polynomial};
+
+    use super::Root;
+
+    /// The monic polynomial with the given roots.
+    fn from_roots(roots: &[f64]) -> Polynomial {
+        roots.iter().fold(Polynomial::constant(1.), |p, &x| {
+            &p * &Polynomial::new(vec![-x, 1.])
+        })
+    }
+
+    fn assert_roots(p: &Polynomial, expected: &[(Complex, usize)], tolerance: f64) {
+        let roots = p.roots().unwrap();
+        assert_eq!(roots.len(), expected.len(), "{roots:?}");
+        for (
+            Root {
+                value,
+                multiplicity,
+            },
+            &(z, m),
+        ) in roots.iter().zip(expected)
+        {
+            assert!(
+                value.sub(z).norm() <= tolerance * z.norm().max(1.),
+                "{value:?} instead of {z:?} in {roots:?}"
+            );
+            assert_eq!(*multiplicity, m, "{roots:?}");
+        }
+    }
+
+    /// Real roots are exactly real and the others come in exact conjugate pairs.
+    fn assert_conjugate_symmetric(roots: &[Root]) {
+        for root in roots {
+            let conjugate = roots
+                .iter()
+                .filter(|x| x.value == root.value.conj())
+                .map(|x| x.multiplicity)
+                .sum::<usize>();
+            assert_eq!(
+                conjugate, root.multiplicity,
+                "{:?} in {roots:?}",
+                root.value
+            );
+        }
+    }
+
+    fn real(roots: &[f64]) -> Vec<(Complex, usize)> {
+        roots.iter().map(|&x| (Complex::from(x), 1)).collect()
+    }
+
+    #[test]
+    fn closed_forms() {
+        let (i, one) = (Complex::new(0., 1.), Complex::from(1.));
+        assert_roots(&from_roots(&[2.5]), &real(&[2.5]), 1e-15);
+        assert_roots(&from_roots(&[-3., 4.]), &real(&[-3., 4.]), 1e-15);
+        assert_roots(
+            &Polynomial::new(vec![1., 0., 1.]),
+            &[(i.conj(), 1), (i, 1)],
+            1e-15,
+        );
+        assert_roots(&from_roots(&[-1., 0.5, 7.]), &real(&[-1., 0.5, 7.]), 1e-14);
+        // x^3 - 1
+        let third = Complex::new(-0.5, 3f64.sqrt() / 2.);
+        assert_roots(
+            &Polynomial::new(vec![-1., 0., 0., 1.]),
+            &[(third.conj(), 1), (third, 1), (one, 1)],
+            1e-14,
+        );
+        assert_roots(
+            &from_roots(&[-2., -1., 3., 5.]),
+            &real(&[-2., -1., 3., 5.]),
+            1e-14,
+        );
+        // x^4 + 4 = (x^2 - 2x + 2)(x^2 + 2x + 2)
+        assert_roots(
+            &Polynomial::new(vec![4., 0., 0., 0., 1.]),
+            &[
+                (Complex::new(-1., -1.), 1),
+                (Complex::new(-1., 1.), 1),
+                (Complex::new(1., -1.), 1),
+                (Complex::new(1., 1.), 1),
+            ],
+            1e-14,
+        );
+    }
+
+    #[test]
+    fn aberth() {
+        let expected = [-4., -1.5, 0.25, 2., 3., 10.];
+        assert_roots(&from_roots(&expected), &real(&expected), 1e-12);
+
+        // x^7 - 1, whose roots are the seventh roots of unity.
+        let p = Polynomial::new([vec![-1.], vec![0.; 6], vec![1.]].concat());
+        let roots = p.roots().unwrap();
+        assert_eq!(roots.len(), 7);
+        for root in &roots {
+            assert!((root.value.norm() - 1.).abs() < 1e-14, "{roots:?}");
+        }
+        assert_eq!(roots[6].value, Complex::from(1.));
+        assert_conjugate_symmetric(&roots);
+
+        // (x - 1)(x - 2)...(x - 12), where rounding easily pushes roots off the real axis.
+        let wilkinson = from_roots(&(1..=12).map(f64::from).collect::<Vec<_>>());
+        let roots = wilkinson.roots().unwrap();
+        assert_eq!(roots.len(), 12, "{roots:?}");
+        for (k, root) in (1..).zip(&roots) {
+            assert_eq!(root.value.im, 0., "{roots:?}");
+            assert!((root.value.re - k as f64).abs() < 1e-6, "{roots:?}");
+        }
+    }
+
+    #[test]
+    fn multiplicities() {
+        // (x - 1)^3 (x + 2)
+        let p = &from_roots(&[1.; 3]) * &from_roots(&[-2.]);
+        assert_roots(
+            &p,
+            &[(Complex::from(-2.), 1), (Complex::from(1.), 3)],
+            1e-12,
+        );
+        // x^2 (x^2 + 1)^2 (x - 3)
+        let square = Polynomial::new(vec![1., 0., 1.]).pow(2);
+        let p = &(&square * &from_roots(&[3.])) * &Polynomial::new(vec![0., 0., 1.]);
+        let i = Complex::new(0., 1.);
+        assert_roots(
+            &p,
+            &[
+                (i.conj(), 2),
+                (Complex::default(), 2),
+                (i, 2),
+                (Complex::from(3.), 1),
+            ],
+            1e-12,
+        );
+        assert_conjugate_symmetric(&p.roots().unwrap());
+    }
+
+    #[test]
+    fn clustered_roots() {
+        // Rounding in p(z) of about 1e-15 moves roots 1e-3 apart by |p(z) / p'(z)| ~ 1e-9.
+        let expected = [1., 1.001, 1.002, 5.];
+        assert_roots(&from_roots(&expected), &real(&expected), 1e-8);
+        let expected = [-1e-3, 1e-3, 0.5, 0.501, 0.502, 2.];
+        assert_roots(&from_roots(&expected), &real(&expected), 1e-8);
+
+        // A close conjugate pair next to a real root: x^3 - 2x^2 + (1 + 1e-8) x.
+        let p = Polynomial::new(vec![1e-6, 1. + 1e-8, -2., 1.]);
+        let roots = p.roots().unwrap();
+        assert_eq!(roots.len(), 3);
+        assert_conjugate_symmetric(&roots);
+        for root in &roots {
+            assert!(p.eval_complex(root.value).norm() < 1e-12, "{roots:?}");
+        }
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let error: MyError = Polynomial::default()
+            .roots()
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(error, MyError::ZeroPolynomial));
+        assert_eq!(Polynomial::constant(2.).roots().unwrap(), []);
+    }
+}
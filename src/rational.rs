@@ -0,0 +1,383 @@
+//! Exact evaluation with arbitrary precision rational numbers.
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{Constant, FunctionTerm, MyError, Number, UnaryOperation, Value};
+
+/// The most bits [`Number::pow`] lets a result have, as larger powers take too long to compute.
+const MAX_BITS: u64 = 1 << 20;
+
+/// A fraction of two big integers, always in lowest terms with a positive denominator.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rational(pub(crate) BigRational);
+
+impl Rational {
+    pub fn new(numerator: impl Into<BigInt>, denominator: impl Into<BigInt>) -> Result<Rational> {
+        let denominator = denominator.into();
+        if denominator.is_zero() {
+            Err(MyError::DivisionByZero)?
+        }
+        Ok(Rational(BigRational::new(numerator.into(), denominator)))
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        self.0.numer()
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        self.0.denom()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.0.is_integer()
+    }
+
+    /// The closest `f64`.
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(value: i64) -> Self {
+        Rational(BigRational::from_integer(value.into()))
+    }
+}
+
+impl From<BigInt> for Rational {
+    fn from(value: BigInt) -> Self {
+        Rational(BigRational::from_integer(value))
+    }
+}
+
+impl From<Rational> for FunctionTerm {
+    fn from(value: Rational) -> Self {
+        FunctionTerm::Value(Value::Rational(value))
+    }
+}
+
+/// Reads integers, fractions like `-1/3` and decimals like `0.125`.
+impl FromStr for Rational {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || MyError::InvalidNumber {
+            literal: text.to_string(),
+            position: 0,
+        };
+
+        if let Some((numerator, denominator)) = text.split_once('/') {
+            let numerator: BigInt = numerator.trim().parse().map_err(|_| invalid())?;
+            let denominator: BigInt = denominator.trim().parse().map_err(|_| invalid())?;
+            return Rational::new(numerator, denominator);
+        }
+
+        let text = text.trim();
+        let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            Err(invalid())?
+        }
+        let digits: BigInt = format!("{integer}{fraction}")
+            .parse()
+            .map_err(|_| invalid())?;
+        let scale = BigInt::from(10).pow(fraction.len() as u32);
+        Rational::new(digits, scale)
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.is_integer() {
+            true => write!(f, "{}", self.numerator()),
+            false => write!(f, "{}/{}", self.numerator(), self.denominator()),
+        }
+    }
+}
+
+fn irrational(term: FunctionTerm) -> MyError {
+    MyError::Irrational {
+        expression: term.to_string(),
+    }
+}
+
+/// The exact `n`-th root of a non-negative `x`, if it is rational.
+fn root(x: &BigRational, n: u32) -> Option<BigRational> {
+    let exact = |x: &BigInt| Some(x.nth_root(n)).filter(|root| root.pow(n) == *x);
+    Some(BigRational::new(exact(x.numer())?, exact(x.denom())?))
+}
+
+/// The logarithm of the positive `x` to the positive `base`, if it is rational.
+///
+/// It is expanded into a continued fraction, exactly. If the logarithm is rational, `x` and the
+/// base are powers of a common number and so is every intermediate value, which then never grows
+/// beyond the inputs. Growing beyond them means the logarithm is irrational.
+fn logarithm(x: &BigRational, base: &BigRational) -> Option<BigRational> {
+    let bits = |x: &BigRational| x.numer().bits() + x.denom().bits();
+
+    let mut negate = false;
+    let (mut x, mut base) = (x.clone(), base.clone());
+    if x < BigRational::one() {
+        x = x.recip();
+        negate = !negate;
+    }
+    if base < BigRational::one() {
+        base = base.recip();
+        negate = !negate;
+    }
+    if x.is_one() {
+        return Some(BigRational::zero());
+    }
+    let max_bits = bits(&x).max(bits(&base));
+
+    // log_b(x) = k + log_b(x / b^k) = k + 1 / log_(x / b^k)(b), with b^k <= x < b^(k + 1).
+    let mut terms = Vec::new();
+    loop {
+        let mut k = BigInt::zero();
+        while x >= base {
+            x /= &base;
+            k += 1;
+            if bits(&x) > max_bits {
+                return None;
+            }
+        }
+        terms.push(BigRational::from_integer(k));
+
+        if x.is_one() {
+            let mut terms = terms.into_iter().rev();
+            let last = terms.next()?;
+            let result = terms.fold(last, |result, term| term + result.recip());
+            return Some(if negate { -result } else { result });
+        }
+        std::mem::swap(&mut x, &mut base);
+    }
+}
+
+/// Exact arithmetic. Operations whose result is irrational fail with [`MyError::Irrational`].
+///
+/// Literals are read as the shortest decimal that rounds to them, so `0.1` is exactly 1/10.
+impl Number for Rational {
+    fn from_f64(x: f64) -> Result<Self> {
+        if !x.is_finite() {
+            Err(irrational(x.into()))?
+        }
+        // Displaying an `f64` never uses an exponent.
+        x.to_string().parse()
+    }
+
+    fn from_constant(constant: Constant) -> Result<Self> {
+        Err(irrational(FunctionTerm::Value(Value::Constant(constant))))?
+    }
+
+    fn from_rational(x: &Rational) -> Result<Self> {
+        Ok(x.clone())
+    }
+
+    fn plus(self, other: Self) -> Result<Self> {
+        Ok(Rational(self.0 + other.0))
+    }
+
+    fn minus(self, other: Self) -> Result<Self> {
+        Ok(Rational(self.0 - other.0))
+    }
+
+    fn multiply(self, other: Self) -> Result<Self> {
+        Ok(Rational(self.0 * other.0))
+    }
+
+    fn divide(self, other: Self) -> Result<Self> {
+        if other.0.is_zero() {
+            Err(MyError::DivisionByZero)?
+        }
+        Ok(Rational(self.0 / other.0))
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        let (base, exponent) = (self.0, exponent.0);
+
+        if base.is_one() || exponent.is_zero() {
+            return Ok(Rational::from(1));
+        }
+        if base.is_zero() && exponent.is_negative() {
+            Err(MyError::DivisionByZero)?
+        }
+        if base.is_zero() {
+            return Ok(Rational::from(0));
+        }
+
+        // Roots of negative numbers are not real, and no root of a degree beyond u32 can be
+        // exact as the base would have more bits than that.
+        let result = exponent
+            .denom()
+            .to_u32()
+            .filter(|_| !base.is_negative() || exponent.is_integer())
+            .and_then(|degree| root(&base, degree));
+        let Some(result) = result else {
+            Err(irrational(
+                FunctionTerm::from(Rational(base)).pow(Rational(exponent).into()),
+            ))?
+        };
+
+        // The power has about `power` times the bits of the root, which must stay in bounds.
+        let bits = match result.abs().is_one() {
+            true => 0,
+            false => result.numer().bits() + result.denom().bits(),
+        };
+        let power = exponent.numer().magnitude().to_u32();
+        let Some(power) = power.filter(|&n| bits.saturating_mul(n.into()) <= MAX_BITS) else {
+            Err(MyError::ExponentTooLarge {
+                exponent: exponent.to_string(),
+            })?
+        };
+        let result = Rational(num_traits::Pow::pow(result, power));
+        match exponent.is_negative() {
+            true => Rational::from(1).divide(result),
+            false => Ok(result),
+        }
+    }
+
+    fn unary(self, operation: UnaryOperation) -> Result<Self> {
+        let value = self.to_f64();
+        let x = self.0;
+        let (zero, one) = (BigRational::zero(), BigRational::one());
+        let out_of_domain = |x: &BigRational| MyError::OutOfDomain {
+            operation,
+            value: Rational(x.clone()).to_string(),
+        };
+
+        let result = match operation {
+            UnaryOperation::Negate => -x,
+            UnaryOperation::Abs => x.abs(),
+            UnaryOperation::Sqrt if x.is_negative() => Err(MyError::RootOfNegative { value })?,
+            UnaryOperation::Ln | UnaryOperation::Log(_) if !x.is_positive() => {
+                Err(MyError::LogarithmOfNonPositive { value })?
+            }
+            UnaryOperation::Log(base) if base <= 0. || base == 1. => {
+                Err(MyError::InvalidLogarithmBase { base })?
+            }
+            UnaryOperation::Asin | UnaryOperation::Acos if x.abs() > one => Err(out_of_domain(&x))?,
+            UnaryOperation::Acosh if x < one => Err(out_of_domain(&x))?,
+            UnaryOperation::Atanh if x.abs() >= one => Err(out_of_domain(&x))?,
+            UnaryOperation::Sqrt => match root(&x, 2) {
+                Some(root) => root,
+                None => Err(irrational(FunctionTerm::from(Rational(x)).unary(operation)))?,
+            },
+            UnaryOperation::Log(base) => match logarithm(&x, &Rational::from_f64(base)?.0) {
+                Some(result) => result,
+                None => Err(irrational(FunctionTerm::from(Rational(x)).unary(operation)))?,
+            },
+            UnaryOperation::Floor => x.floor(),
+            UnaryOperation::Ceil => x.ceil(),
+            UnaryOperation::Round => x.round(),
+            UnaryOperation::Sign => BigRational::from_integer(x.numer().signum()),
+            // By the Lindemann–Weierstrass theorem the remaining operations are irrational for
+            // every rational argument except the one they map to 0 or 1.
+            UnaryOperation::Sin
+            | UnaryOperation::Tan
+            | UnaryOperation::Asin
+            | UnaryOperation::Atan
+            | UnaryOperation::Sinh
+            | UnaryOperation::Tanh
+            | UnaryOperation::Asinh
+            | UnaryOperation::Atanh
+                if x.is_zero() =>
+            {
+                zero
+            }
+            UnaryOperation::Exp | UnaryOperation::Cos | UnaryOperation::Cosh if x.is_zero() => one,
+            UnaryOperation::Ln | UnaryOperation::Acos | UnaryOperation::Acosh if x.is_one() => zero,
+            operation => Err(irrational(FunctionTerm::from(Rational(x)).unary(operation)))?,
+        };
+
+        Ok(Rational(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{FunctionTerm, MyError};
+
+    use super::Rational;
+
+    fn exact(input: &str) -> anyhow::Result<Rational> {
+        let term: FunctionTerm = input.parse().unwrap();
+        term.solve(&HashMap::new())
+    }
+
+    fn error(input: &str) -> MyError {
+        exact(input).unwrap_err().downcast().unwrap()
+    }
+
+    fn rational(text: &str) -> Rational {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn literals_are_exact() {
+        assert_eq!(exact("0.1 + 0.2").unwrap(), rational("3/10"));
+        assert_eq!(exact("1 / 3 + 1 / 6").unwrap(), rational("1/2"));
+        assert_eq!(exact("0.125 * 8").unwrap(), Rational::from(1));
+        assert_eq!(rational("-2/4").to_string(), "-1/2");
+        assert_eq!(rational("1.250").to_string(), "5/4");
+        assert!(rational("6/3").is_integer());
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(exact("(4 / 9)^1.5").unwrap(), rational("8/27"));
+        assert_eq!(exact("2^-3").unwrap(), rational("1/8"));
+        assert_eq!(exact("(-8)^3").unwrap(), Rational::from(-512));
+        assert_eq!(exact("sqrt(0.25)").unwrap(), rational("1/2"));
+        assert_eq!(exact("0^0").unwrap(), Rational::from(1));
+
+        assert!(matches!(error("2^0.5"), MyError::Irrational { .. }));
+        assert!(matches!(error("(-8)^(1 / 3)"), MyError::Irrational { .. }));
+        assert!(matches!(error("0^-1"), MyError::DivisionByZero));
+        assert!(matches!(error("sqrt(-4)"), MyError::RootOfNegative { .. }));
+    }
+
+    #[test]
+    fn large_exponents_are_refused() {
+        assert!(matches!(
+            error("3^10000000"),
+            MyError::ExponentTooLarge { .. }
+        ));
+        assert!(matches!(
+            error("(1 / 2)^-10000000"),
+            MyError::ExponentTooLarge { .. }
+        ));
+        // Powers of ±1 have no size at all.
+        assert_eq!(exact("(-1)^10000001").unwrap(), Rational::from(-1));
+        assert_eq!(exact("2^1000").unwrap().numerator().bits(), 1001);
+    }
+
+    #[test]
+    fn logarithms() {
+        assert_eq!(exact("log(4, 8)").unwrap(), rational("3/2"));
+        assert_eq!(exact("log(8, 1 / 4)").unwrap(), rational("-2/3"));
+        assert_eq!(exact("log(0.5, 32)").unwrap(), Rational::from(-5));
+        assert_eq!(exact("log(10, 1)").unwrap(), Rational::from(0));
+        assert_eq!(exact("ln(1) + exp(0)").unwrap(), Rational::from(1));
+
+        assert!(matches!(error("log(2, 3)"), MyError::Irrational { .. }));
+        assert!(matches!(
+            error("log(4, 0)"),
+            MyError::LogarithmOfNonPositive { .. }
+        ));
+        assert!(matches!(
+            error("log(1, 5)"),
+            MyError::InvalidLogarithmBase { .. }
+        ));
+        assert!(matches!(error("sin(1)"), MyError::Irrational { .. }));
+        assert!(matches!(error("pi"), MyError::Irrational { .. }));
+    }
+}
@@ -0,0 +1,451 @@
+//! Reverse mode automatic differentiation. A forward sweep records every operation on a tape,
+//! one backward sweep over the tape then yields the derivatives by all arguments at once.
+
+use anyhow::Result;
+
+use crate::{dual::scaled, Function, FunctionTerm, MyError, Operation, UnaryOperation};
+
+/// The numbers a tape is recorded with: plain values for gradients, and values with a
+/// derivative in one direction for Hessian-vector products.
+trait Scalar: Clone {
+    fn constant(x: f64) -> Self;
+    fn value(&self) -> f64;
+    /// The result of a function at `self`, given the function's value and derivative there.
+    fn lift(&self, value: f64, derivative: f64) -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+    fn div(&self, other: &Self) -> Self;
+    fn pow(&self, exponent: &Self) -> Self;
+    fn is_zero(&self) -> bool;
+}
+
+impl Scalar for f64 {
+    fn constant(x: f64) -> Self {
+        x
+    }
+
+    fn value(&self) -> f64 {
+        *self
+    }
+
+    fn lift(&self, value: f64, _: f64) -> Self {
+        value
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        self / other
+    }
+
+    fn pow(&self, exponent: &Self) -> Self {
+        self.powf(*exponent)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0.
+    }
+}
+
+/// A value and its derivative in one direction.
+#[derive(Debug, Clone, Copy)]
+struct Tangent {
+    value: f64,
+    tangent: f64,
+}
+
+impl Scalar for Tangent {
+    fn constant(x: f64) -> Self {
+        Tangent {
+            value: x,
+            tangent: 0.,
+        }
+    }
+
+    fn value(&self) -> f64 {
+        self.value
+    }
+
+    fn lift(&self, value: f64, derivative: f64) -> Self {
+        Tangent {
+            value,
+            tangent: scaled(self.tangent, derivative),
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Tangent {
+            value: self.value + other.value,
+            tangent: self.tangent + other.tangent,
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Tangent {
+            value: self.value * other.value,
+            tangent: scaled(self.tangent, other.value) + scaled(other.tangent, self.value),
+        }
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        let value = self.value / other.value;
+        Tangent {
+            value,
+            tangent: scaled(self.tangent, 1. / other.value)
+                - scaled(other.tangent, value / other.value),
+        }
+    }
+
+    fn pow(&self, exponent: &Self) -> Self {
+        let (x, y) = (self.value, exponent.value);
+        let value = x.powf(y);
+        Tangent {
+            value,
+            tangent: scaled(self.tangent, y * x.powf(y - 1.))
+                + scaled(exponent.tangent, value * x.ln()),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.value == 0. && self.tangent == 0.
+    }
+}
+
+/// The first and second derivative of `operation` at `x`, where it evaluates to `value`.
+fn derivatives(operation: UnaryOperation, x: f64, value: f64) -> (f64, f64) {
+    match operation {
+        UnaryOperation::Negate => (-1., 0.),
+        UnaryOperation::Abs if x == 0. => (0., 0.),
+        UnaryOperation::Abs => (x.signum(), 0.),
+        UnaryOperation::Sqrt => (0.5 / value, -0.25 / (x * value)),
+        UnaryOperation::Exp => (value, value),
+        UnaryOperation::Ln => (1. / x, -1. / (x * x)),
+        UnaryOperation::Log(base) => (1. / (x * base.ln()), -1. / (x * x * base.ln())),
+        UnaryOperation::Sin => (x.cos(), -value),
+        UnaryOperation::Cos => (-x.sin(), -value),
+        UnaryOperation::Tan => {
+            let derivative = 1. + value * value;
+            (derivative, 2. * value * derivative)
+        }
+        UnaryOperation::Asin | UnaryOperation::Acos => {
+            let root = (1. - x * x).sqrt();
+            let sign = match operation {
+                UnaryOperation::Asin => 1.,
+                _ => -1.,
+            };
+            (sign / root, sign * x / (root * root * root))
+        }
+        UnaryOperation::Atan => {
+            let derivative = 1. / (1. + x * x);
+            (derivative, -2. * x * derivative * derivative)
+        }
+        UnaryOperation::Sinh => (x.cosh(), value),
+        UnaryOperation::Cosh => (x.sinh(), value),
+        UnaryOperation::Tanh => {
+            let derivative = 1. - value * value;
+            (derivative, -2. * value * derivative)
+        }
+        UnaryOperation::Asinh => {
+            let root = (x * x + 1.).sqrt();
+            (1. / root, -x / (root * root * root))
+        }
+        UnaryOperation::Acosh => {
+            let root = (x * x - 1.).sqrt();
+            (1. / root, -x / (root * root * root))
+        }
+        UnaryOperation::Atanh => {
+            let derivative = 1. / (1. - x * x);
+            (derivative, 2. * x * derivative * derivative)
+        }
+        // Piecewise constant.
+        UnaryOperation::Floor
+        | UnaryOperation::Ceil
+        | UnaryOperation::Round
+        | UnaryOperation::Sign => (0., 0.),
+    }
+}
+
+struct Node<T> {
+    value: T,
+    /// The operands with the partial derivative of this node by each of them, left out for
+    /// operands that do not depend on any argument.
+    operands: Vec<(usize, T)>,
+    constant: bool,
+}
+
+/// Every intermediate value of one evaluation, the arguments first.
+struct Tape<T> {
+    nodes: Vec<Node<T>>,
+    result: usize,
+}
+
+impl<T: Scalar> Tape<T> {
+    fn record(function: &Function, args: Vec<T>) -> Result<Tape<T>> {
+        let mut tape = Tape {
+            nodes: args
+                .into_iter()
+                .map(|value| Node {
+                    value,
+                    operands: Vec::new(),
+                    constant: false,
+                })
+                .collect(),
+            result: 0,
+        };
+        tape.result = tape.push_term(function, &function.term)?;
+        Ok(tape)
+    }
+
+    fn push(&mut self, value: T, operands: Vec<(usize, T)>) -> usize {
+        self.nodes.push(Node {
+            value,
+            constant: operands.is_empty(),
+            operands,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Records `term` and returns the index of its node.
+    fn push_term(&mut self, function: &Function, term: &FunctionTerm) -> Result<usize> {
+        let index = match term {
+            FunctionTerm::Variable(x) => function
+                .arguments
+                .iter()
+                .position(|argument| argument == x)
+                .ok_or(MyError::NoSuchVariable { variable: *x })?,
+            FunctionTerm::Value(x) => self.push(T::constant(x.get()?), Vec::new()),
+            FunctionTerm::Unary { term, operation } => {
+                let operand = self.push_term(function, term)?;
+                let x = self.nodes[operand].value.clone();
+                let value = operation.apply(x.value())?;
+                let (first, second) = derivatives(*operation, x.value(), value);
+
+                let mut operands = Vec::new();
+                if !self.nodes[operand].constant {
+                    operands.push((operand, x.lift(first, second)));
+                }
+                self.push(x.lift(value, first), operands)
+            }
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let (left, right) = (
+                    self.push_term(function, left)?,
+                    self.push_term(function, right)?,
+                );
+                let x = self.nodes[left].value.clone();
+                let y = self.nodes[right].value.clone();
+                // Only for the domain checks, the value itself is computed on `T`.
+                operation.apply(x.value(), y.value())?;
+
+                let (one, minus_one) = (T::constant(1.), T::constant(-1.));
+                let value = match operation {
+                    Operation::Plus => x.add(&y),
+                    Operation::Minus => x.add(&y.mul(&minus_one)),
+                    Operation::Multiply => x.mul(&y),
+                    Operation::Divide => x.div(&y),
+                    Operation::Pow => x.pow(&y),
+                };
+
+                // (x^y)' = y * x^(y - 1) * x' + x^y * ln(x) * y'
+                let mut operands = Vec::new();
+                if !self.nodes[left].constant {
+                    let partial = match operation {
+                        Operation::Plus | Operation::Minus => one.clone(),
+                        Operation::Multiply => y.clone(),
+                        Operation::Divide => one.div(&y),
+                        Operation::Pow => y.mul(&x.pow(&y.add(&minus_one))),
+                    };
+                    operands.push((left, partial));
+                }
+                if !self.nodes[right].constant {
+                    let partial = match operation {
+                        Operation::Plus => one,
+                        Operation::Minus => minus_one,
+                        Operation::Multiply => x,
+                        Operation::Divide => value.div(&y).mul(&minus_one),
+                        Operation::Pow => value.mul(&x.lift(x.value().ln(), 1. / x.value())),
+                    };
+                    operands.push((right, partial));
+                }
+                self.push(value, operands)
+            }
+        };
+
+        Ok(index)
+    }
+
+    /// The derivatives of the result by every argument, from one backward sweep.
+    fn backward(&self, arity: usize) -> Vec<T> {
+        let mut adjoints = vec![T::constant(0.); self.nodes.len()];
+        adjoints[self.result] = T::constant(1.);
+
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            if adjoints[index].is_zero() {
+                continue;
+            }
+            let adjoint = adjoints[index].clone();
+            for (operand, partial) in &node.operands {
+                adjoints[*operand] = adjoints[*operand].add(&adjoint.mul(partial));
+            }
+        }
+
+        adjoints.truncate(arity);
+        adjoints
+    }
+
+    fn result(&self) -> &T {
+        &self.nodes[self.result].value
+    }
+}
+
+impl Function {
+    fn expect_arguments(&self, given: usize) -> Result<()> {
+        if given != self.arguments.len() {
+            Err(MyError::ArityMismatch {
+                expected: self.arguments.len(),
+                given,
+            })?
+        }
+        Ok(())
+    }
+
+    /// The value and the gradient by reverse mode, with a cost independent of the number of
+    /// arguments, see [`Function::eval_with_gradient`] for forward mode.
+    pub fn eval_with_gradient_reverse(&self, args: &[f64]) -> Result<(f64, Vec<f64>)> {
+        self.expect_arguments(args.len())?;
+        let tape = Tape::record(self, args.to_vec())?;
+        Ok((*tape.result(), tape.backward(args.len())))
+    }
+
+    /// The Hessian at `args` times `direction`, without building the Hessian.
+    ///
+    /// This is forward mode over the reverse sweep: the gradient is computed along with its
+    /// derivative in `direction`, which is the product.
+    pub fn hessian_vector_product(&self, args: &[f64], direction: &[f64]) -> Result<Vec<f64>> {
+        self.expect_arguments(args.len())?;
+        self.expect_arguments(direction.len())?;
+
+        let args = args
+            .iter()
+            .zip(direction)
+            .map(|(&value, &tangent)| Tangent { value, tangent })
+            .collect();
+        let tape = Tape::record(self, args)?;
+        Ok(tape
+            .backward(direction.len())
+            .into_iter()
+            .map(|x| x.tangent)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, MyError};
+
+    const FUNCTIONS: [(&str, &[f64]); 7] = [
+        ("x^2 * y + 3 * y", &[1.5, -2.]),
+        ("x * x * x - x / y", &[0.7, 1.3]),
+        ("sin(x * y) / (1 + x^2)", &[0.3, 0.7]),
+        ("x^y + y^x", &[1.7, 2.5]),
+        ("exp(-x) * ln(y) - sqrt(x * y) + log(3, x)", &[0.4, 2.2]),
+        (
+            "tanh(x) * asinh(y) + atan(x / y) * acos(y / 2)",
+            &[0.8, 0.9],
+        ),
+        (
+            "x * y * z - cosh(z)^3 + tan(x) * atanh(y / 2)",
+            &[1., 0.5, 0.25],
+        ),
+    ];
+
+    fn error<T: std::fmt::Debug>(result: anyhow::Result<T>) -> MyError {
+        result.unwrap_err().downcast().unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        let error = (actual - expected).abs() / expected.abs().max(1.);
+        assert!(error < 1e-10, "{what}: {actual} instead of {expected}");
+    }
+
+    #[test]
+    fn gradients_agree_with_forward_mode() {
+        for (f, args) in FUNCTIONS {
+            let f: Function = f.parse().unwrap();
+            let (value, gradient) = f.eval_with_gradient_reverse(args).unwrap();
+            let (expected_value, expected) = f.eval_with_gradient(args).unwrap();
+            assert_eq!(value, expected_value);
+            for (actual, expected) in gradient.into_iter().zip(expected) {
+                assert_close(actual, expected, &f.term.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn hessian_vector_products_agree_with_derivatives() {
+        for (f, args) in FUNCTIONS {
+            let f: Function = f.parse().unwrap();
+            let direction: Vec<f64> = (0..args.len()).map(|i| 0.5 - i as f64).collect();
+            let product = f.hessian_vector_product(args, &direction).unwrap();
+
+            for (&x, actual) in f.arguments.iter().zip(product) {
+                let expected: f64 = f
+                    .arguments
+                    .iter()
+                    .zip(&direction)
+                    .map(|(&y, v)| {
+                        let second = f.derivative(x).derivative(y);
+                        second.solve_args_in_order(args.to_vec()).unwrap() * v
+                    })
+                    .sum();
+                assert_close(actual, expected, &format!("{} by {x}", f.term));
+            }
+        }
+    }
+
+    #[test]
+    fn constant_parts_are_skipped() {
+        let f: Function = "x * (2 + 3) + sqrt(16)".parse().unwrap();
+        assert_eq!(
+            f.eval_with_gradient_reverse(&[2.]).unwrap(),
+            (14., vec![5.])
+        );
+        assert_eq!(f.hessian_vector_product(&[2.], &[1.]).unwrap(), [0.]);
+    }
+
+    #[test]
+    fn errors() {
+        let f: Function = "x / y".parse().unwrap();
+        assert!(matches!(
+            error(f.eval_with_gradient_reverse(&[1.])),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        assert!(matches!(
+            error(f.hessian_vector_product(&[1., 2.], &[1., 2., 3.])),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 3
+            }
+        ));
+        assert!(matches!(
+            error(f.eval_with_gradient_reverse(&[1., 0.])),
+            MyError::DivisionByZero
+        ));
+        assert!(matches!(
+            error(f.hessian_vector_product(&[1., 0.], &[1., 1.])),
+            MyError::DivisionByZero
+        ));
+    }
+}
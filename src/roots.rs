@@ -0,0 +1,520 @@
+//! Numerical root finding for functions of one argument.
+
+use anyhow::Result;
+
+use crate::{Function, MyError, Program, Range};
+
+/// How many pieces [`Function::roots_in`] checks for sign changes and touching roots.
+const SAMPLES: usize = 1000;
+
+/// Roots closer than this, relative to their size, are reported once.
+const SAME_ROOT: f64 = 1e-9;
+
+/// How small a local minimum of `|f|` has to be, relative to its neighbours, to count as a root.
+const TOUCHING: f64 = 1e-8;
+
+/// How small a local minimum of `|f|` has to be, relative to its neighbours, for the two pieces
+/// around it to be sampled again more finely.
+const REFINE: f64 = 0.5;
+
+/// How many pieces the two pieces around a small minimum are split into.
+const REFINEMENT: usize = 16;
+
+/// When the iterative solvers stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// How close to the root the result has to be, on top of a few units of rounding.
+    pub x: f64,
+    /// A point where the function is at most this far from zero is accepted right away.
+    pub y: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            x: 1e-12,
+            y: 0.,
+            max_iterations: 200,
+        }
+    }
+}
+
+impl Tolerance {
+    /// The distance to the root that is good enough near `x`.
+    fn at(&self, x: f64) -> f64 {
+        self.x + 2. * f64::EPSILON * x.abs()
+    }
+
+    fn no_convergence(&self, estimate: f64) -> MyError {
+        MyError::NoConvergence {
+            iterations: self.max_iterations,
+            estimate,
+        }
+    }
+}
+
+/// How [`Function::newton`] gets the derivative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Derivative {
+    /// Differentiates the term once and compiles the result.
+    Symbolic,
+    /// Uses forward mode automatic differentiation in every step.
+    Automatic,
+}
+
+impl Function {
+    fn single_argument(&self) -> Result<Program> {
+        let program = self.compile()?;
+        program.expect_arity(1)?;
+        Ok(program)
+    }
+
+    /// Finds a root in `[a, b]` by halving the interval, see [`Program::bisection`].
+    pub fn bisection(&self, a: f64, b: f64, tolerance: Tolerance) -> Result<f64> {
+        self.single_argument()?.bisection(a, b, tolerance)
+    }
+
+    /// Finds a root by the secant method, see [`Program::secant`].
+    pub fn secant(&self, x0: f64, x1: f64, tolerance: Tolerance) -> Result<f64> {
+        self.single_argument()?.secant(x0, x1, tolerance)
+    }
+
+    /// Finds a root in `[a, b]` by Brent's method, see [`Program::brent`].
+    pub fn brent(&self, a: f64, b: f64, tolerance: Tolerance) -> Result<f64> {
+        self.single_argument()?.brent(a, b, tolerance)
+    }
+
+    /// Finds a root by Newton's method starting from `x`.
+    pub fn newton(&self, x: f64, derivative: Derivative, tolerance: Tolerance) -> Result<f64> {
+        let f = self.single_argument()?;
+        let symbolic = match derivative {
+            Derivative::Symbolic => Some(self.derivative(self.arguments[0]).compile()?),
+            Derivative::Automatic => None,
+        };
+
+        let mut x = x;
+        for _ in 0..tolerance.max_iterations {
+            let (fx, slope) = match &symbolic {
+                Some(derivative) => (f.eval(&[x])?, derivative.eval(&[x])?),
+                None => {
+                    let (fx, gradient) = self.eval_with_gradient(&[x])?;
+                    (fx, gradient[0])
+                }
+            };
+            if fx.abs() <= tolerance.y {
+                return Ok(x);
+            }
+            if slope == 0. {
+                Err(MyError::ZeroDerivative { x })?
+            }
+
+            let step = fx / slope;
+            x -= step;
+            if !x.is_finite() {
+                break;
+            }
+            if step.abs() <= tolerance.at(x) {
+                return Ok(x);
+            }
+        }
+
+        Err(tolerance.no_convergence(x))?
+    }
+
+    /// Finds all real roots in `[a, b]`, sorted and each only once.
+    ///
+    /// The interval is split into a thousand pieces. Every sign change between the ends of a
+    /// piece is refined with Brent's method, and every local minimum of `|f|` that comes close to
+    /// zero is refined as a root of the derivative, which finds roots where the function only
+    /// touches zero. Sign changes across a pole or a jump are not roots and are left out. Where
+    /// `|f|` dips well below the ends of the same sign around it, the two pieces at the dip are
+    /// split again and again, which finds pairs of roots closer together than the pieces.
+    pub fn roots_in(&self, a: f64, b: f64) -> Result<Vec<f64>> {
+        let f = self.single_argument()?;
+        let x = Range::new(a.min(b), a.max(b), SAMPLES + 1);
+        let mut roots = Vec::new();
+        self.scan(&f, &mut None, x, &mut roots)?;
+
+        // Split pieces can land on the stretch around a root where the function rounds to zero
+        // more than once.
+        let piece = (x.end - x.start) / SAMPLES as f64;
+        roots.sort_by(f64::total_cmp);
+        roots.dedup_by(|x, y| {
+            let distance = (*x - *y).abs();
+            distance <= SAME_ROOT * (1. + y.abs())
+                || distance < piece / 2. && f.eval(&[(*x + *y) / 2.]).is_ok_and(|y| y == 0.)
+        });
+        Ok(roots)
+    }
+
+    /// Collects the roots between the points of `x` into `roots`, see [`Function::roots_in`].
+    fn scan(
+        &self,
+        f: &Program,
+        derivative: &mut Option<Program>,
+        x: Range,
+        roots: &mut Vec<f64>,
+    ) -> Result<()> {
+        let tolerance = Tolerance::default();
+        let points: Vec<[f64; 1]> = (0..x.count).map(|i| [x.at(i)]).collect();
+        // Points where the function is undefined split the interval.
+        let y: Vec<Option<f64>> = f
+            .eval_rows(&points)
+            .into_iter()
+            .map(|y| y.ok().filter(|y| y.is_finite()))
+            .collect();
+        let x: Vec<f64> = points.into_iter().map(|[x]| x).collect();
+
+        roots.extend((0..x.len()).filter(|&i| y[i] == Some(0.)).map(|i| x[i]));
+
+        for i in 1..x.len() {
+            let (Some(left), Some(right)) = (y[i - 1], y[i]) else {
+                continue;
+            };
+            if left == 0. || right == 0. || left.signum() == right.signum() {
+                continue;
+            }
+            let Ok(root) = f.brent(x[i - 1], x[i], tolerance) else {
+                continue;
+            };
+            // At a pole or a jump the function does not get any closer to zero. An end right on
+            // the root is already about as close as the root itself.
+            let (near, far) = (left.abs().min(right.abs()), left.abs().max(right.abs()));
+            if f.eval(&[root])
+                .is_ok_and(|y| y.abs() < near / 2. || y.abs() <= TOUCHING * far)
+            {
+                roots.push(root);
+            }
+        }
+
+        for i in 1..x.len().saturating_sub(1) {
+            let (Some(left), Some(middle), Some(right)) = (y[i - 1], y[i], y[i + 1]) else {
+                continue;
+            };
+            // Ends of the same sign around a smaller `|f|` can hide roots between them.
+            let hidden = middle == 0.
+                || middle.signum() == left.signum() && middle.signum() == right.signum();
+            let (left, middle, right) = (left.abs(), middle.abs(), right.abs());
+
+            let width = x[i + 1] - x[i - 1];
+            if hidden
+                && middle <= left.min(right)
+                && middle < REFINE * left.max(right)
+                && width > 2. * SAME_ROOT * (1. + x[i].abs())
+            {
+                let pieces = Range::new(x[i - 1], x[i + 1], REFINEMENT + 1);
+                self.scan(f, derivative, pieces, roots)?;
+            }
+
+            let touching = hidden && middle != 0. && middle < left.min(right);
+            if !touching {
+                continue;
+            }
+
+            let derivative = match derivative {
+                Some(derivative) => &*derivative,
+                None => derivative.insert(self.derivative(self.arguments[0]).compile()?),
+            };
+            // Without a sign change of the derivative this is not a smooth minimum.
+            let Ok(root) = derivative.brent(x[i - 1], x[i + 1], tolerance) else {
+                continue;
+            };
+            if f.eval(&[root])
+                .is_ok_and(|y| y.abs() <= TOUCHING * left.max(right))
+            {
+                roots.push(root);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Program {
+    /// Finds a root in `[a, b]` by halving the interval, `f(a)` and `f(b)` must differ in sign.
+    pub fn bisection(&self, a: f64, b: f64, tolerance: Tolerance) -> Result<f64> {
+        self.expect_arity(1)?;
+        let (mut a, mut b) = (a.min(b), a.max(b));
+        let (mut fa, fb) = (self.eval(&[a])?, self.eval(&[b])?);
+        if fa == 0. {
+            return Ok(a);
+        }
+        if fb == 0. {
+            return Ok(b);
+        }
+        if fa.signum() == fb.signum() {
+            Err(MyError::NoSignChange { a, b })?
+        }
+
+        for _ in 0..tolerance.max_iterations {
+            let middle = a + (b - a) / 2.;
+            let fm = self.eval(&[middle])?;
+            if fm.abs() <= tolerance.y || (b - a) / 2. <= tolerance.at(middle) {
+                return Ok(middle);
+            }
+            if fm.signum() == fa.signum() {
+                (a, fa) = (middle, fm);
+            } else {
+                b = middle;
+            }
+        }
+
+        Err(tolerance.no_convergence(a + (b - a) / 2.))?
+    }
+
+    /// Finds a root by the secant method from the two starting points `x0` and `x1`.
+    pub fn secant(&self, x0: f64, x1: f64, tolerance: Tolerance) -> Result<f64> {
+        self.expect_arity(1)?;
+        let (mut x0, mut x1) = (x0, x1);
+        let mut f0 = self.eval(&[x0])?;
+
+        for _ in 0..tolerance.max_iterations {
+            let f1 = self.eval(&[x1])?;
+            if f1.abs() <= tolerance.y {
+                return Ok(x1);
+            }
+            if f1 == f0 {
+                Err(MyError::ZeroDerivative { x: x1 })?
+            }
+
+            let step = f1 * (x1 - x0) / (f1 - f0);
+            (x0, f0) = (x1, f1);
+            x1 -= step;
+            if !x1.is_finite() {
+                break;
+            }
+            if step.abs() <= tolerance.at(x1) {
+                return Ok(x1);
+            }
+        }
+
+        Err(tolerance.no_convergence(x1))?
+    }
+
+    /// Finds a root in `[a, b]` by Brent's method, which combines the guaranteed convergence of
+    /// bisection with the speed of inverse quadratic interpolation. `f(a)` and `f(b)` must differ
+    /// in sign.
+    pub fn brent(&self, a: f64, b: f64, tolerance: Tolerance) -> Result<f64> {
+        self.expect_arity(1)?;
+        let (mut a, mut b) = (a, b);
+        let (mut fa, mut fb) = (self.eval(&[a])?, self.eval(&[b])?);
+        if fa == 0. {
+            return Ok(a);
+        }
+        if fb == 0. {
+            return Ok(b);
+        }
+        if fa.signum() == fb.signum() {
+            Err(MyError::NoSignChange {
+                a: a.min(b),
+                b: a.max(b),
+            })?
+        }
+
+        // `b` is the best estimate, `c` the other end of the bracket and `a` the previous `b`.
+        let (mut c, mut fc) = (a, fa);
+        let (mut d, mut e) = (b - a, b - a);
+
+        for _ in 0..tolerance.max_iterations {
+            if fb.signum() == fc.signum() {
+                (c, fc) = (a, fa);
+                (d, e) = (b - a, b - a);
+            }
+            if fc.abs() < fb.abs() {
+                (a, fa) = (b, fb);
+                (b, fb) = (c, fc);
+                (c, fc) = (a, fa);
+            }
+
+            let limit = tolerance.at(b) / 2.;
+            let middle = (c - b) / 2.;
+            if fb.abs() <= tolerance.y || middle.abs() <= limit {
+                return Ok(b);
+            }
+
+            if e.abs() >= limit && fa.abs() > fb.abs() {
+                // Inverse quadratic interpolation, or the secant rule with only two points.
+                let s = fb / fa;
+                let (mut p, mut q) = match a == c {
+                    true => (2. * middle * s, 1. - s),
+                    false => {
+                        let (q, r) = (fa / fc, fb / fc);
+                        (
+                            s * (2. * middle * q * (q - r) - (b - a) * (r - 1.)),
+                            (q - 1.) * (r - 1.) * (s - 1.),
+                        )
+                    }
+                };
+                if p > 0. {
+                    q = -q;
+                }
+                p = p.abs();
+
+                // Only accept the step if it stays well inside the bracket and shrinks fast enough.
+                if 2. * p < (3. * middle * q - (limit * q).abs()).min((e * q).abs()) {
+                    e = d;
+                    d = p / q;
+                } else {
+                    (d, e) = (middle, middle);
+                }
+            } else {
+                (d, e) = (middle, middle);
+            }
+
+            (a, fa) = (b, fb);
+            b += match d.abs() > limit {
+                true => d,
+                false => limit.copysign(middle),
+            };
+            fb = self.eval(&[b])?;
+        }
+
+        Err(tolerance.no_convergence(b))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{Function, MyError};
+
+    use super::{Derivative, Tolerance};
+
+    fn function(input: &str) -> Function {
+        input.parse().unwrap()
+    }
+
+    fn error(result: anyhow::Result<f64>) -> MyError {
+        result.unwrap_err().downcast().unwrap()
+    }
+
+    /// Within the default tolerance of the root.
+    fn assert_root(actual: f64, expected: f64, what: &str) {
+        let error = (actual - expected).abs();
+        assert!(
+            error <= 1e-12 + 4. * f64::EPSILON * expected.abs(),
+            "{what}: {actual} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn solvers_agree() {
+        let tolerance = Tolerance::default();
+        for (f, a, b, expected) in [
+            ("x^2 - 2", 0., 3., 2f64.sqrt()),
+            ("cos(x) - x", 0., 1., 0.7390851332151607),
+            ("exp(x) - 10", 1., 4., 10f64.ln()),
+            ("x^3 - 2 * x - 5", 2., 3., 2.0945514815423265),
+        ] {
+            let f = function(f);
+            assert_root(f.bisection(a, b, tolerance).unwrap(), expected, "bisection");
+            assert_root(f.bisection(b, a, tolerance).unwrap(), expected, "bisection");
+            assert_root(f.brent(a, b, tolerance).unwrap(), expected, "brent");
+            assert_root(f.brent(b, a, tolerance).unwrap(), expected, "brent");
+            assert_root(f.secant(a, b, tolerance).unwrap(), expected, "secant");
+            for derivative in [Derivative::Symbolic, Derivative::Automatic] {
+                let root = f.newton(b, derivative, tolerance).unwrap();
+                assert_root(root, expected, "newton");
+            }
+        }
+    }
+
+    #[test]
+    fn tolerances() {
+        let f = function("x - 0.3");
+        assert_eq!(f.bisection(0.3, 1., Tolerance::default()).unwrap(), 0.3);
+
+        // A loose tolerance on the value stops at the first point that is close enough.
+        let loose = Tolerance {
+            y: 0.5,
+            ..Tolerance::default()
+        };
+        assert_eq!(f.bisection(0., 1., loose).unwrap(), 0.5);
+        assert_eq!(f.newton(0.7, Derivative::Symbolic, loose).unwrap(), 0.7);
+
+        let coarse = Tolerance {
+            x: 1e-3,
+            ..Tolerance::default()
+        };
+        let root = f.bisection(0., 1., coarse).unwrap();
+        assert!((root - 0.3).abs() <= 1e-3 && root != 0.3);
+    }
+
+    #[test]
+    fn all_roots() {
+        for (f, a, b, expected) in [
+            ("x^3 - x", -2., 2., &[-1., 0., 1.][..]),
+            ("sin(x)", 10., -1., &[0., PI, 2. * PI, 3. * PI]),
+            ("x^2 + 1", -5., 5., &[]),
+            // A pole and a jump change the sign without a root.
+            ("1 / (x - 0.3)", 0., 1., &[]),
+            ("tan(x)", 1., 5., &[PI]),
+            ("sign(x - 0.35) + 0.5", 0., 1., &[]),
+            ("sqrt(x - 0.5) - 0.25", 0., 1., &[0.5625]),
+            // Roots where the function only touches zero.
+            ("(x - 0.3)^2", 0., 1., &[0.3]),
+            ("(x - 0.3)^2 * (x - 0.8)", 0., 1., &[0.3, 0.8]),
+            ("cos(x) + 1", 0., 4., &[PI]),
+            // Roots closer together than the pieces.
+            ("x^4 - 0.0001 * x^2", -1., 1., &[-0.01, 0., 0.01]),
+            ("(x - 0.5) * (x - 0.5001)", 0., 1., &[0.5, 0.5001]),
+            ("(x - 0.51) * (x - 0.510001)", 0., 1., &[0.51, 0.510001]),
+            ("(x - 0.123)^2 - 0.00000001", 0., 1., &[0.1229, 0.1231]),
+        ] {
+            let roots = function(f).roots_in(a, b).unwrap();
+            assert_eq!(roots.len(), expected.len(), "{f}: {roots:?}");
+            for (&actual, &expected) in roots.iter().zip(expected) {
+                let error = (actual - expected).abs();
+                assert!(error <= 1e-7 * expected.abs().max(1.), "{f}: {roots:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let tolerance = Tolerance::default();
+        let f = function("x^2 + 1");
+        assert!(matches!(
+            error(f.bisection(-1., 2., tolerance)),
+            MyError::NoSignChange { a: -1., b: 2. }
+        ));
+        assert!(matches!(
+            error(f.brent(2., -1., tolerance)),
+            MyError::NoSignChange { a: -1., b: 2. }
+        ));
+        assert!(matches!(
+            error(f.secant(-1., 1., tolerance)),
+            MyError::ZeroDerivative { x: 1. }
+        ));
+        assert!(matches!(
+            error(f.newton(0., Derivative::Automatic, tolerance)),
+            MyError::ZeroDerivative { x: 0. }
+        ));
+        let few = Tolerance {
+            max_iterations: 20,
+            ..tolerance
+        };
+        assert!(matches!(
+            error(f.newton(0.5, Derivative::Symbolic, few)),
+            MyError::NoConvergence { iterations: 20, .. }
+        ));
+        assert!(matches!(
+            error(function("x - 1").bisection(0., 3., few)),
+            MyError::NoConvergence { iterations: 20, .. }
+        ));
+
+        assert!(matches!(
+            error(function("x * y").brent(0., 1., tolerance)),
+            MyError::ArityMismatch {
+                expected: 2,
+                given: 1
+            }
+        ));
+        assert!(matches!(
+            error(function("1 / x").newton(0., Derivative::Symbolic, tolerance)),
+            MyError::DivisionByZero
+        ));
+    }
+}
@@ -0,0 +1,510 @@
+//! Algebraic simplification: constant folding, identities, like terms and powers.
+
+use crate::{Constant, Function, FunctionTerm, Operation, UnaryOperation, Value};
+
+impl Function {
+    /// An equivalent function with a (usually) smaller term.
+    pub fn simplify(&self) -> Function {
+        Function {
+            arguments: self.arguments.clone(),
+            term: self.term.simplify(),
+        }
+    }
+}
+
+impl FunctionTerm {
+    /// Folds constants, removes identities and collects like terms and powers of the same base.
+    ///
+    /// Powers are only combined where that keeps the points at which the term is undefined, so
+    /// `x / x` and `(x^0.5)^2` stay as they are. Cancelling whole terms may still define the
+    /// result where the original was not, as in `0 * ln(x)` or `ln(x) - ln(x)`.
+    pub fn simplify(&self) -> FunctionTerm {
+        match self {
+            // Constants stay symbolic, `2 * pi` reads better than `6.283185307179586`, and
+            // rationals stay exact.
+            Self::Variable(_) | Self::Value(Value::Constant(_) | Value::Rational(_)) => {
+                self.clone()
+            }
+            Self::Value(x) => match x.get() {
+                Ok(x) => folded(x),
+                Err(_) => self.clone(),
+            },
+            Self::Unary {
+                term,
+                operation: UnaryOperation::Negate,
+            } => {
+                let term = term.simplify();
+                let mut product = Product::one();
+                product.coefficient = -1.;
+                product.multiply(&term, false);
+                product.into_term().unwrap_or_else(|| -term)
+            }
+            Self::Unary { term, operation } => {
+                let term = term.simplify();
+                if let Some(x) = term.literal() {
+                    if let Ok(result) = operation.apply(x) {
+                        if result.is_finite() {
+                            return folded(result);
+                        }
+                    }
+                }
+                if *operation == UnaryOperation::Ln
+                    && term == Self::Value(Value::Constant(Constant::E))
+                {
+                    return 1.0.into();
+                }
+                Self::Unary {
+                    term: term.into(),
+                    operation: *operation,
+                }
+            }
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => {
+                let (left, right) = (left.simplify(), right.simplify());
+
+                if let (Some(left), Some(right)) = (left.literal(), right.literal()) {
+                    let calculation = Value::_Calculation {
+                        left: Value::Literal(left).into(),
+                        right: Value::Literal(right).into(),
+                        operation: *operation,
+                    };
+                    if let Ok(x) = calculation.get::<f64>() {
+                        if x.is_finite() {
+                            return folded(x);
+                        }
+                    }
+                }
+
+                // `x / 0` fails to evaluate, which simplifying must not change.
+                if *operation == Operation::Divide && right.literal() == Some(0.) {
+                    return left / right;
+                }
+
+                let simplified = match operation {
+                    Operation::Plus | Operation::Minus => {
+                        let mut sum = Vec::new();
+                        add_summands(&mut sum, &left, 1.);
+                        add_summands(&mut sum, &right, operation.sign());
+                        sum_into_term(sum)
+                    }
+                    Operation::Multiply | Operation::Divide => {
+                        let mut product = Product::one();
+                        product.multiply(&left, false);
+                        product.multiply(&right, *operation == Operation::Divide);
+                        product.into_term()
+                    }
+                    Operation::Pow => return simplify_pow(left, right),
+                };
+                // A coefficient that overflowed, or came from a kept `x / 0`, has no literal.
+                simplified.unwrap_or_else(|| Self::Calculation {
+                    left: left.into(),
+                    right: right.into(),
+                    operation: *operation,
+                })
+            }
+        }
+    }
+
+    pub(crate) fn literal(&self) -> Option<f64> {
+        match self {
+            Self::Value(Value::Literal(x)) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+/// The literal for a folded constant, where adding zero turns `-0` into `0`.
+fn folded(x: f64) -> FunctionTerm {
+    (x + 0.).into()
+}
+
+impl Operation {
+    fn sign(&self) -> f64 {
+        match self {
+            Self::Minus => -1.,
+            _ => 1.,
+        }
+    }
+}
+
+fn simplify_pow(base: FunctionTerm, exponent: FunctionTerm) -> FunctionTerm {
+    match (base.literal(), exponent.literal()) {
+        (_, Some(0.)) => 1.0.into(),
+        (_, Some(1.)) => base,
+        (Some(1.), _) => 1.0.into(),
+        (Some(b), Some(e)) if b == 0. && e > 0. => 0.0.into(),
+        // (b^c)^d = b^(c * d) only holds for integer d, e.g. (x^2)^0.5 = |x|.
+        (_, Some(e)) if e.fract() == 0. => match base {
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation: Operation::Pow,
+            } => {
+                let product = (right.as_ref().clone() * e.into()).simplify();
+                match combinable(&left, [&right, &exponent], &product) {
+                    true => simplify_pow(*left, product),
+                    false => FunctionTerm::Calculation {
+                        left,
+                        right,
+                        operation: Operation::Pow,
+                    }
+                    .pow(exponent),
+                }
+            }
+            base => base.pow(exponent),
+        },
+        _ => base.pow(exponent),
+    }
+}
+
+/// The bases for which a power is defined, from all numbers to only the positive ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Domain {
+    Any,
+    Nonzero,
+    NonNegative,
+    Positive,
+}
+
+impl Domain {
+    /// The bases in both domains.
+    fn and(self, other: Domain) -> Domain {
+        match (self, other) {
+            (Domain::Any, x) | (x, Domain::Any) => x,
+            (x, y) if x == y => x,
+            _ => Domain::Positive,
+        }
+    }
+
+    /// Where `base^exponent` is defined, `None` if the exponent is not a number.
+    fn of_power(exponent: &FunctionTerm) -> Option<Domain> {
+        let e = exponent.literal()?;
+        Some(match (e.fract() == 0., e < 0.) {
+            (true, false) => Domain::Any,
+            (true, true) => Domain::Nonzero,
+            (false, false) => Domain::NonNegative,
+            (false, true) => Domain::Positive,
+        })
+    }
+
+    /// What is known of the value of an already simplified term.
+    fn of_value(term: &FunctionTerm) -> Domain {
+        match term {
+            FunctionTerm::Value(Value::Literal(x)) if *x > 0. => Domain::Positive,
+            FunctionTerm::Value(Value::Literal(x)) if *x < 0. => Domain::Nonzero,
+            FunctionTerm::Value(Value::Constant(Constant::I)) => Domain::Nonzero,
+            FunctionTerm::Value(Value::Constant(_)) => Domain::Positive,
+            FunctionTerm::Unary {
+                operation: UnaryOperation::Exp,
+                ..
+            } => Domain::Positive,
+            FunctionTerm::Unary {
+                operation: UnaryOperation::Abs | UnaryOperation::Sqrt,
+                ..
+            } => Domain::NonNegative,
+            _ => Domain::Any,
+        }
+    }
+}
+
+/// Whether the powers of `base` to both `parts` can be replaced by one power to `combined`
+/// without it being defined for more bases, as `x / x = x^0` would be at zero.
+fn combinable(base: &FunctionTerm, parts: [&FunctionTerm; 2], combined: &FunctionTerm) -> bool {
+    let known = Domain::of_value(base);
+    if known == Domain::Positive {
+        return true;
+    }
+    match (
+        Domain::of_power(parts[0]),
+        Domain::of_power(parts[1]),
+        Domain::of_power(combined),
+    ) {
+        (Some(x), Some(y), Some(z)) => known.and(x).and(y) == known.and(z),
+        _ => false,
+    }
+}
+
+/// A coefficient times a product of powers.
+struct Product {
+    coefficient: f64,
+    factors: Vec<(FunctionTerm, FunctionTerm)>,
+}
+
+impl Product {
+    fn one() -> Product {
+        Product {
+            coefficient: 1.,
+            factors: Vec::new(),
+        }
+    }
+
+    /// Multiplies by an already simplified term, or divides by it if `invert` is set.
+    fn multiply(&mut self, term: &FunctionTerm, invert: bool) {
+        match term {
+            FunctionTerm::Value(Value::Literal(x)) if invert => self.coefficient /= x,
+            FunctionTerm::Value(Value::Literal(x)) => self.coefficient *= x,
+            FunctionTerm::Unary {
+                term,
+                operation: UnaryOperation::Negate,
+            } => {
+                self.coefficient = -self.coefficient;
+                self.multiply(term, invert);
+            }
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation: operation @ (Operation::Multiply | Operation::Divide),
+            } => {
+                self.multiply(left, invert);
+                self.multiply(right, invert != (*operation == Operation::Divide));
+            }
+            FunctionTerm::Calculation {
+                left,
+                right,
+                operation: Operation::Pow,
+            } => self.multiply_power(left, right, invert),
+            term => self.multiply_power(term, &1.0.into(), invert),
+        }
+    }
+
+    fn multiply_power(&mut self, base: &FunctionTerm, exponent: &FunctionTerm, invert: bool) {
+        let exponent = match invert {
+            true => (FunctionTerm::from(-1.) * exponent.clone()).simplify(),
+            false => exponent.clone(),
+        };
+
+        let like = self.factors.iter_mut().find(|(b, _)| b == base);
+        match like {
+            Some((_, e)) => {
+                let sum = (e.clone() + exponent.clone()).simplify();
+                match combinable(base, [e, &exponent], &sum) {
+                    true => *e = sum,
+                    false => self.factors.push((base.clone(), exponent)),
+                }
+            }
+            None => self.factors.push((base.clone(), exponent)),
+        }
+    }
+
+    /// Whether both products have the same factors, regardless of order.
+    fn is_like(&self, other: &Product) -> bool {
+        self.factors.len() == other.factors.len()
+            && self.factors.iter().all(|x| other.factors.contains(x))
+    }
+
+    /// The product as a term, `None` if the coefficient is not finite.
+    fn into_term(self) -> Option<FunctionTerm> {
+        if !self.coefficient.is_finite() {
+            return None;
+        }
+        if self.coefficient == 0. {
+            return Some(0.0.into());
+        }
+
+        let mut numerator = Vec::new();
+        let mut denominator = Vec::new();
+        let mut negate = false;
+
+        // Prefer `x / 3` over `0.3333333333333333 * x` and `-x` over `-1 * x`.
+        let reciprocal = (1. / self.coefficient).round();
+        if self.coefficient.abs() < 1. && 1. / reciprocal == self.coefficient {
+            denominator.push(reciprocal.abs().into());
+            negate = reciprocal < 0.;
+        } else if self.coefficient == -1. && !self.factors.is_empty() {
+            negate = true;
+        } else if self.coefficient != 1. {
+            numerator.push(self.coefficient.into());
+        }
+
+        for (base, exponent) in self.factors {
+            match exponent.literal() {
+                Some(0.) => {}
+                Some(e) if e < 0. => denominator.push(simplify_pow(base, (-e).into())),
+                _ => numerator.push(simplify_pow(base, exponent)),
+            }
+        }
+
+        let numerator = multiply_all(numerator);
+        let term = match denominator.is_empty() {
+            true => numerator,
+            false => numerator / multiply_all(denominator),
+        };
+        Some(match negate {
+            true => -term,
+            false => term,
+        })
+    }
+}
+
+fn multiply_all(factors: Vec<FunctionTerm>) -> FunctionTerm {
+    factors
+        .into_iter()
+        .reduce(|x, y| x * y)
+        .unwrap_or(1.0.into())
+}
+
+/// Flattens an already simplified sum into its summands, scaled by `sign`.
+fn add_summands(sum: &mut Vec<Product>, term: &FunctionTerm, sign: f64) {
+    if let FunctionTerm::Calculation {
+        left,
+        right,
+        operation: operation @ (Operation::Plus | Operation::Minus),
+    } = term
+    {
+        add_summands(sum, left, sign);
+        add_summands(sum, right, sign * operation.sign());
+        return;
+    }
+    if let FunctionTerm::Unary {
+        term,
+        operation: UnaryOperation::Negate,
+    } = term
+    {
+        add_summands(sum, term, -sign);
+        return;
+    }
+
+    let mut product = Product::one();
+    product.multiply(term, false);
+    product.coefficient *= sign;
+
+    match sum.iter_mut().find(|x| x.is_like(&product)) {
+        Some(like) => like.coefficient += product.coefficient,
+        None => sum.push(product),
+    }
+}
+
+/// The sum as a term, `None` if a coefficient is not finite.
+fn sum_into_term(sum: Vec<Product>) -> Option<FunctionTerm> {
+    // Constants go last, as in `x^2 + 1`.
+    let (constants, summands): (Vec<_>, Vec<_>) = sum
+        .into_iter()
+        .filter(|x| x.coefficient != 0.)
+        .partition(|x| x.factors.is_empty());
+
+    let mut result: Option<FunctionTerm> = None;
+    for mut summand in summands.into_iter().chain(constants) {
+        result = Some(match result {
+            None => summand.into_term()?,
+            Some(result) if summand.coefficient < 0. => {
+                summand.coefficient = -summand.coefficient;
+                result - summand.into_term()?
+            }
+            Some(result) => result + summand.into_term()?,
+        });
+    }
+
+    Some(result.unwrap_or(0.0.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{FunctionTerm, Identifier};
+
+    fn simplified(input: &str) -> String {
+        let term: FunctionTerm = input.parse().unwrap();
+        term.simplify().to_string()
+    }
+
+    /// The value at `x`, `None` where the term is undefined.
+    fn value(term: &FunctionTerm, x: f64) -> Option<f64> {
+        let args = HashMap::from([(Identifier::new("x"), x), (Identifier::new("y"), 0.7)]);
+        term.solve::<f64>(&args).ok().filter(|x| x.is_finite())
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        assert_eq!(simplified("2 + 3 * 4"), "14");
+        assert_eq!(simplified("x * (2 - 1) + 3^2"), "x + 9");
+        assert_eq!(simplified("ln(e) + sin(0)"), "1");
+        // Named constants stay symbolic.
+        assert_eq!(simplified("2 * pi"), "2 * pi");
+    }
+
+    #[test]
+    fn identities_are_removed() {
+        for input in [
+            "x * 1", "1 * x", "x + 0", "0 + x", "x - 0", "x / 1", "x^1", "-(-x)",
+        ] {
+            assert_eq!(simplified(input), "x", "{input}");
+        }
+        assert_eq!(simplified("x^0"), "1");
+        assert_eq!(simplified("0 * x"), "0");
+        assert_eq!(simplified("1^x"), "1");
+    }
+
+    #[test]
+    fn like_terms_are_collected() {
+        assert_eq!(simplified("x + x"), "2 * x");
+        assert_eq!(simplified("2*x + 3*x - x"), "4 * x");
+        assert_eq!(simplified("x*y + y*x"), "2 * x * y");
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("1 + x + 2"), "x + 3");
+        assert_eq!(simplified("x * 0.5"), "x / 2");
+    }
+
+    #[test]
+    fn powers_are_combined() {
+        assert_eq!(simplified("x * x"), "x^2");
+        assert_eq!(simplified("x^2 * x^3"), "x^5");
+        assert_eq!(simplified("(x^2)^3"), "x^6");
+        assert_eq!(simplified("(x^2)^-1"), "x^(-2)");
+        assert_eq!(simplified("x^-1 * x^-1"), "1 / x^2");
+        assert_eq!(simplified("e^x * e^x"), "e^(2 * x)");
+        assert_eq!(simplified("abs(x)^0.5 * abs(x)^0.5"), "abs(x)");
+    }
+
+    #[test]
+    fn division_by_zero_is_kept() {
+        assert_eq!(simplified("x / 0"), "x / 0");
+        assert_eq!(simplified("(x - x) / (x - x)"), "0 / 0");
+        assert_eq!(simplified("2 * (x / 0)"), "2 * (x / 0)");
+        let term: FunctionTerm = "x / 0 + 1".parse().unwrap();
+        assert_eq!(value(&term.simplify(), 1.), None);
+    }
+
+    #[test]
+    fn undefined_points_stay_undefined() {
+        for (input, x) in [
+            ("x / x", 0.),
+            ("x^2 / x", 0.),
+            ("x^3 / x^2", 0.),
+            ("(x^0.5)^2", -1.),
+            ("x^0.5 * x^0.5", -1.),
+            ("x^-1 * x", 0.),
+            ("(x^0.5)^-2", -1.),
+        ] {
+            let term: FunctionTerm = input.parse().unwrap();
+            assert_eq!(value(&term, x), None, "{input} is defined at {x}");
+            assert_eq!(
+                value(&term.simplify(), x),
+                None,
+                "{input} became defined at {x}"
+            );
+        }
+    }
+
+    #[test]
+    fn values_are_kept() {
+        for input in [
+            "x / x",
+            "(x + 1)^2 * (x + 1)^-1",
+            "3 * x * y / (6 * y) - x / 2",
+            "sqrt(x) * sqrt(x) + x^0.5",
+            "-(x - y) + -(-y)",
+            "2^x * 2^(x + 1)",
+        ] {
+            let term: FunctionTerm = input.parse().unwrap();
+            let simple = term.simplify();
+            for x in [0.3, 1.9, 4.] {
+                let (a, b) = (value(&term, x).unwrap(), value(&simple, x).unwrap());
+                assert!((a - b).abs() <= 1e-12 * a.abs().max(1.), "{input} at {x}");
+            }
+        }
+    }
+}
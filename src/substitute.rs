@@ -0,0 +1,186 @@
+//! Replacing variables by terms, and composing functions.
+
+use anyhow::Result;
+
+use crate::{Function, FunctionTerm, Identifier, MyError};
+
+impl Function {
+    /// The function with `variable` replaced by `term` everywhere.
+    ///
+    /// The variables of `term` take the place of `variable` in the argument list, except those
+    /// that already are arguments, which are the same variable as the argument of that name.
+    pub fn substitute(&self, variable: Identifier, term: &FunctionTerm) -> Result<Function> {
+        let Some(position) = self.arguments.iter().position(|&x| x == variable) else {
+            Err(MyError::NoSuchVariable { variable })?
+        };
+
+        let mut arguments = self.arguments.clone();
+        arguments.remove(position);
+        let new = term
+            .variables()
+            .into_iter()
+            .filter(|x| !arguments.contains(x))
+            .collect::<Vec<_>>();
+        arguments.splice(position..position, new);
+
+        Ok(Function {
+            arguments,
+            term: self.term.substitute(variable, term),
+        })
+    }
+
+    /// The function `self(inner(...), ...)`, which passes the result of `inner` as the first
+    /// argument of `self`.
+    ///
+    /// The arguments are those of `inner` followed by the remaining ones of `self`. Where the
+    /// remaining ones share a name with an argument of `inner` they are renamed, so `f(x, a)`
+    /// composed with `g(x, a)` takes the arguments `x, a, a_1`.
+    pub fn compose(&self, inner: &Function) -> Result<Function> {
+        let Some((&variable, rest)) = self.arguments.split_first() else {
+            Err(MyError::ArityMismatch {
+                expected: 0,
+                given: 1,
+            })?
+        };
+
+        let mut taken = [
+            &self.arguments[..],
+            &inner.arguments,
+            &self.term.variables(),
+            &inner.term.variables(),
+        ]
+        .concat();
+        let mut outer = self.clone();
+        for &argument in rest.iter().filter(|x| inner.arguments.contains(x)) {
+            let renamed = fresh(argument, &taken);
+            taken.push(renamed);
+            outer = outer.substitute(argument, &FunctionTerm::Variable(renamed))?;
+        }
+
+        Ok(Function {
+            arguments: [&inner.arguments[..], &outer.arguments[1..]].concat(),
+            term: outer.term.substitute(variable, &inner.term),
+        })
+    }
+}
+
+impl FunctionTerm {
+    /// The term with every occurrence of `variable` replaced by `term`.
+    pub fn substitute(&self, variable: Identifier, term: &FunctionTerm) -> FunctionTerm {
+        match self {
+            Self::Variable(x) if *x == variable => term.clone(),
+            Self::Variable(_) | Self::Value(_) => self.clone(),
+            Self::Calculation {
+                left,
+                right,
+                operation,
+            } => Self::Calculation {
+                left: left.substitute(variable, term).into(),
+                right: right.substitute(variable, term).into(),
+                operation: *operation,
+            },
+            Self::Unary {
+                term: inner,
+                operation,
+            } => inner.substitute(variable, term).unary(*operation),
+        }
+    }
+}
+
+/// The first of `name_1`, `name_2`, … that is not taken.
+fn fresh(name: Identifier, taken: &[Identifier]) -> Identifier {
+    (1..)
+        .map(|i| Identifier::new(&format!("{name}_{i}")))
+        .find(|x| !taken.contains(x))
+        .expect("there are only finitely many names taken")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Function, FunctionTerm, Identifier, MyError};
+
+    /// A function of the given arguments, in that order.
+    fn function(arguments: &[&str], term: &str) -> Function {
+        Function {
+            arguments: arguments.iter().map(|x| Identifier::new(x)).collect(),
+            term: term.parse().unwrap(),
+        }
+    }
+
+    fn names(f: &Function) -> Vec<&str> {
+        f.arguments.iter().map(|x| x.name()).collect()
+    }
+
+    fn eval(f: &Function, args: &[f64]) -> f64 {
+        f.solve_args_in_order(args.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn substitution() {
+        let f = function(&["x", "y"], "x^2 + y");
+        let term: FunctionTerm = "2 * t".parse().unwrap();
+        let g = f.substitute(Identifier::new("x"), &term).unwrap();
+        assert_eq!(names(&g), ["t", "y"]);
+        assert_eq!(eval(&g, &[3., 1.]), 37.);
+
+        // The variables of the term that already are arguments stay where they are.
+        let term: FunctionTerm = "y + s".parse().unwrap();
+        let g = f.substitute(Identifier::new("x"), &term).unwrap();
+        assert_eq!(names(&g), ["s", "y"]);
+        assert_eq!(eval(&g, &[1., 2.]), 11.);
+
+        let g = f.substitute(Identifier::new("y"), &3.0.into()).unwrap();
+        assert_eq!(names(&g), ["x"]);
+        assert_eq!(eval(&g, &[2.]), 7.);
+
+        let error = f.substitute(Identifier::new("z"), &term).unwrap_err();
+        assert!(matches!(
+            error.downcast().unwrap(),
+            MyError::NoSuchVariable { variable } if variable.name() == "z"
+        ));
+    }
+
+    #[test]
+    fn composition() {
+        let outer = function(&["u", "c"], "u - c");
+        let inner = function(&["x", "y"], "x * y");
+        let f = outer.compose(&inner).unwrap();
+        assert_eq!(names(&f), ["x", "y", "c"]);
+        assert_eq!(eval(&f, &[2., 3., 1.]), 5.);
+
+        let error = function(&[], "1").compose(&inner).unwrap_err();
+        assert!(matches!(
+            error.downcast().unwrap(),
+            MyError::ArityMismatch {
+                expected: 0,
+                given: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn composition_avoids_capture() {
+        // f(x, a) = x + a after g(x, a) = x * a is g(x, a) + a_1, not g(x, a) + a.
+        let outer = function(&["x", "a"], "x + a");
+        let inner = function(&["x", "a"], "x * a");
+        let f = outer.compose(&inner).unwrap();
+        assert_eq!(names(&f), ["x", "a", "a_1"]);
+        assert_eq!(eval(&f, &[2., 3., 5.]), 11.);
+
+        // Names that are taken already are skipped.
+        let outer = function(&["u", "a"], "u - a");
+        let inner = function(&["a", "a_1"], "10 * a + a_1");
+        let f = outer.compose(&inner).unwrap();
+        assert_eq!(names(&f), ["a", "a_1", "a_2"]);
+        assert_eq!(eval(&f, &[1., 2., 3.]), 9.);
+
+        // So are free variables of either term, which are not arguments.
+        let outer = function(&["u", "a"], "u * a + a_1");
+        let inner = function(&["a"], "a + a_2");
+        let f = outer.compose(&inner).unwrap();
+        assert_eq!(names(&f), ["a", "a_3"]);
+        assert!(f.term.contains(Identifier::new("a_3")));
+        assert!(f.term.contains(Identifier::new("a_1")));
+        assert!(f.term.contains(Identifier::new("a_2")));
+    }
+}